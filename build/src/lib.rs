@@ -0,0 +1,107 @@
+//! Generates the code of [smlang](https://docs.rs/smlang) state machines from build scripts.
+//!
+//! Instead of being expanded by `statemachine_file!` during compilation, the state machine is
+//! written as formatted Rust, which can be inspected in IDEs, reviewed and checked into version
+//! control. The DSL file is the same as for `statemachine_file!`:
+//!
+//! ```no_run
+//! // In `main` of build.rs
+//! let out_dir = std::path::PathBuf::from(std::env::var("OUT_DIR").unwrap());
+//! smlang_build::generate("src/machine.sm", out_dir.join("machine.rs")).unwrap();
+//! ```
+//!
+//! ```ignore
+//! // src/main.rs
+//! include!(concat!(env!("OUT_DIR"), "/machine.rs"));
+//! ```
+//!
+//! The generated code uses the `smlang` crate, which remains a normal dependency.
+
+use proc_macro2::{LineColumn, TokenStream};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// An error while generating a state machine.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading the DSL or writing the generated code failed.
+    Io(PathBuf, io::Error),
+    /// The state machine definition is invalid, with all errors prefixed by their location.
+    Invalid(Vec<String>),
+    /// The generated code could not be parsed for formatting, which is a bug in smlang.
+    Generated(syn::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(path, error) => write!(f, "{}: {}", path.display(), error),
+            Error::Invalid(errors) => write!(f, "{}", errors.join("\n")),
+            Error::Generated(error) => write!(f, "the generated code is not valid Rust: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Generates the code of the state machine defined in the DSL file `input` and writes it as
+/// formatted Rust to `output`.
+///
+/// Cargo is told to rerun the build script when `input` changes, and warnings are reported as
+/// build script warnings.
+pub fn generate<P, Q>(input: P, output: Q) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let (input, output) = (input.as_ref(), output.as_ref());
+    println!("cargo:rerun-if-changed={}", input.display());
+
+    let source =
+        fs::read_to_string(input).map_err(|error| Error::Io(input.to_path_buf(), error))?;
+    let (code, warnings) = generate_string(&source, &input.display().to_string())?;
+    for warning in warnings {
+        println!("cargo:warning={}", warning);
+    }
+
+    fs::write(output, code).map_err(|error| Error::Io(output.to_path_buf(), error))
+}
+
+/// Generates the formatted code of the state machine defined in the DSL `source`, returning the
+/// code and the warnings. `file` is the name used in the locations of errors and warnings.
+pub fn generate_string(source: &str, file: &str) -> Result<(String, Vec<String>), Error> {
+    let location = |start: LineColumn| format!("{}:{}:{}", file, start.line, start.column + 1);
+
+    let tokens: TokenStream = source.parse().map_err(|error: proc_macro2::LexError| {
+        Error::Invalid(vec![format!(
+            "{}: {}",
+            location(error.span().start()),
+            error
+        )])
+    })?;
+
+    let (sm, warnings) = smlang_core::analyse(tokens).map_err(|errors| {
+        Error::Invalid(
+            errors
+                .into_iter()
+                .map(|error| format!("{}: {}", location(error.span().start()), error))
+                .collect(),
+        )
+    })?;
+    let warnings = warnings
+        .into_iter()
+        .map(|warning| format!("{}: {}", location(warning.span.start()), warning.message))
+        .collect();
+
+    let code = smlang_core::codegen::generate_code(&sm);
+    let syntax = syn::parse2(code).map_err(Error::Generated)?;
+    let code = format!(
+        "// Generated by smlang-build from `{}`, do not edit.\n\n{}",
+        file,
+        prettyplease::unparse(&syntax)
+    );
+
+    Ok((code, warnings))
+}
@@ -0,0 +1,38 @@
+use std::path::{Path, PathBuf};
+
+fn machine(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../tests/machines")
+        .join(name)
+}
+
+#[test]
+fn generate_file() {
+    let output = std::env::temp_dir().join("smlang-build-turnstile.rs");
+    smlang_build::generate(machine("turnstile.sm"), &output).unwrap();
+
+    let code = std::fs::read_to_string(&output).unwrap();
+    assert!(code.starts_with("// Generated by smlang-build from `"));
+    assert!(code.contains("pub enum TurnstileStates {"));
+    assert!(code.contains("fn valid_coin(&self) -> Result<bool, ()>;"));
+    syn::parse_file(&code).unwrap();
+}
+
+#[test]
+fn invalid_state_machine() {
+    let source = "transitions: {\n    *Idle + Start = Running,\n    Idle + Start = Stopped,\n}\n";
+    let error = smlang_build::generate_string(source, "machine.sm").unwrap_err();
+    assert!(error.to_string().starts_with(
+        "machine.sm:3:5: Idle + Start: State and event combination specified multiple times"
+    ));
+}
+
+#[test]
+fn warnings() {
+    let source = "transitions: {\n    *Idle + Start = Running,\n    Orphan + Start = Idle,\n}\n";
+    let (_, warnings) = smlang_build::generate_string(source, "machine.sm").unwrap();
+    assert_eq!(
+        warnings,
+        ["machine.sm:3:5: State `Orphan` is unreachable from the starting state `Idle`"]
+    );
+}
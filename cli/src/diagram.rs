@@ -0,0 +1,124 @@
+use smlang_core::diagramgen::generate_diagram;
+use smlang_core::export::{action, guard_string, sorted_states, sorted_transitions};
+use smlang_core::parser::output_state::HISTORY_STATE;
+use smlang_core::parser::ParsedStateMachine;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+pub enum Format {
+    Dot,
+    Mermaid,
+    PlantUml,
+    Svg,
+}
+
+impl Format {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Dot => "gv",
+            Format::Mermaid => "mmd",
+            Format::PlantUml => "puml",
+            Format::Svg => "svg",
+        }
+    }
+}
+
+/// Generates a diagram of the state machine in the given format.
+pub fn generate(sm: &ParsedStateMachine, format: &Format) -> Result<String, String> {
+    match format {
+        Format::Dot => Ok(generate_diagram(sm)),
+        Format::Mermaid => Ok(generate_mermaid(sm)),
+        Format::PlantUml => Ok(generate_plantuml(sm)),
+        Format::Svg => render_svg(&generate_diagram(sm)),
+    }
+}
+
+/// Generates a Mermaid state diagram.
+pub fn generate_mermaid(sm: &ParsedStateMachine) -> String {
+    let mut diagram = String::from("stateDiagram-v2\n");
+    diagram.push_str(&format!("    [*] --> {}\n", sm.starting_state));
+    for line in lines(sm, HISTORY_STATE) {
+        diagram.push_str(&format!("    {}\n", line));
+    }
+    diagram
+}
+
+/// Generates a PlantUML state diagram.
+pub fn generate_plantuml(sm: &ParsedStateMachine) -> String {
+    let mut diagram = String::from("@startuml\n");
+    diagram.push_str(&format!("[*] --> {}\n", sm.starting_state));
+    for line in lines(sm, "[H]") {
+        diagram.push_str(&format!("{}\n", line));
+    }
+    diagram.push_str("@enduml\n");
+    diagram
+}
+
+// The state descriptions and transitions, which use the same syntax in Mermaid and PlantUML.
+// Mermaid has no history pseudo-state, so it is drawn as a state named `H`, as in the dot
+// diagram.
+fn lines(sm: &ParsedStateMachine, history: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    for state in sorted_states(sm) {
+        if let Some(declaration) = sm.state_declarations.get(&state) {
+            let activities = [
+                ("entry", &declaration.entry),
+                ("exit", &declaration.exit),
+                ("do", &declaration.activity),
+            ];
+            for (kind, activity) in activities.iter() {
+                if let Some(activity) = activity {
+                    lines.push(format!("{} : {} / {}", state, kind, action(activity)));
+                }
+            }
+            if declaration.is_final {
+                lines.push(format!("{} --> [*]", state));
+            }
+        }
+
+        for (event, transition) in sorted_transitions(sm, &state) {
+            let mut label = event;
+            if let Some(guard) = &transition.guard {
+                label.push_str(&format!(" [{}]", guard_string(guard)));
+            }
+            if let Some(transition_action) = &transition.action {
+                label.push_str(&format!(" / {}", action(transition_action)));
+            }
+
+            let target = if transition.out_state == HISTORY_STATE {
+                history.to_string()
+            } else {
+                transition.out_state.to_string()
+            };
+            lines.push(format!("{} --> {} : {}", state, target, label));
+        }
+    }
+
+    lines
+}
+
+// Renders a dot diagram with graphviz.
+fn render_svg(diagram: &str) -> Result<String, String> {
+    let mut process = Command::new("dot")
+        .arg("-Tsvg")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|_| "failed to execute `dot`, is graphviz installed?".to_string())?;
+
+    process
+        .stdin
+        .take()
+        .map(|mut stdin| stdin.write_all(diagram.as_bytes()))
+        .transpose()
+        .map_err(|error| format!("failed to write to `dot`: {}", error))?;
+
+    let output = process
+        .wait_with_output()
+        .map_err(|error| format!("failed to run `dot`: {}", error))?;
+    if !output.status.success() {
+        return Err("`dot` failed to render the diagram".to_string());
+    }
+    String::from_utf8(output.stdout).map_err(|_| "`dot` produced invalid UTF-8".to_string())
+}
@@ -0,0 +1,246 @@
+//! `smlang` checks, diagrams and exports state machines without building the crate using them.
+//!
+//! The state machines are read from `.sm` files, containing the DSL as used by
+//! `statemachine_file!`, from SCXML documents as used by `statemachine_scxml!`, and from the
+//! `statemachine!` invocations in `.rs` files.
+
+mod diagram;
+mod source;
+
+use smlang_core::{analyse, export, parser};
+use source::Machine;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+Usage: smlang <COMMAND> [OPTIONS] [PATHS]...
+
+Reads state machines from `.sm` and `.scxml` files and from `statemachine!` invocations in `.rs`
+files. The files of `statemachine_file!` and `statemachine_scxml!` are not followed, pass them or
+a directory containing them. Directories are searched recursively, the default path is the
+current directory.
+
+Commands:
+  check      Report the errors and warnings of the state machines
+  diagram    Generate diagrams of the state machines
+  export     Export the definitions of the state machines
+
+Options:
+  --format <FORMAT>  Diagram format: dot, mermaid, plantuml or svg [default: dot]
+  --json             Export as JSON [default]
+  --scxml            Export as SCXML
+  --output <DIR>     Write `statemachine_<name>.<ext>` files to DIR instead of stdout, required
+                     to export several machines as SCXML. Several machines exported as JSON
+                     to stdout are printed as an array
+  -h, --help         Print this help
+";
+
+enum Command {
+    Check,
+    Diagram(diagram::Format),
+    Export(ExportFormat),
+}
+
+enum ExportFormat {
+    Json,
+    Scxml,
+}
+
+struct Args {
+    command: Command,
+    output: Option<PathBuf>,
+    paths: Vec<PathBuf>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let command = args.next().ok_or("missing command")?;
+    let mut format = None;
+    let mut export = None;
+    let mut output = None;
+    let mut paths = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => format = Some(args.next().ok_or("missing value for `--format`")?),
+            "--json" => export = Some(ExportFormat::Json),
+            "--scxml" => export = Some(ExportFormat::Scxml),
+            "--output" => {
+                output = Some(PathBuf::from(
+                    args.next().ok_or("missing value for `--output`")?,
+                ))
+            }
+            option if option.starts_with('-') => {
+                return Err(format!("unknown option `{}`", option))
+            }
+            path => paths.push(PathBuf::from(path)),
+        }
+    }
+
+    if paths.is_empty() {
+        paths.push(PathBuf::from("."));
+    }
+
+    let command = match command.as_str() {
+        "check" => Command::Check,
+        "diagram" => Command::Diagram(match format.as_deref() {
+            None | Some("dot") => diagram::Format::Dot,
+            Some("mermaid") => diagram::Format::Mermaid,
+            Some("plantuml") => diagram::Format::PlantUml,
+            Some("svg") => diagram::Format::Svg,
+            Some(format) => return Err(format!("unknown diagram format `{}`", format)),
+        }),
+        "export" => Command::Export(export.unwrap_or(ExportFormat::Json)),
+        command => return Err(format!("unknown command `{}`", command)),
+    };
+
+    Ok(Args {
+        command,
+        output,
+        paths,
+    })
+}
+
+fn main() -> ExitCode {
+    let args: Vec<_> = std::env::args().skip(1).collect();
+    if args.is_empty() || args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        print!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+
+    let args = match parse_args(args.into_iter()) {
+        Ok(args) => args,
+        Err(error) => {
+            eprintln!("error: {}\n\n{}", error, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    let mut machines = Vec::new();
+    let mut failures = Vec::new();
+    for path in &args.paths {
+        if let Err(error) = source::load(path, &mut machines, &mut failures) {
+            eprintln!("error: {}", error);
+            return ExitCode::from(2);
+        }
+    }
+    for failure in &failures {
+        eprintln!("error: {}", failure);
+    }
+
+    // A single SCXML document can only hold one state machine
+    if let (Command::Export(ExportFormat::Scxml), None) = (&args.command, &args.output) {
+        if machines.len() > 1 {
+            eprintln!(
+                "error: found {} state machines, exporting more than one as SCXML requires `--output`",
+                machines.len()
+            );
+            return ExitCode::from(2);
+        }
+    }
+
+    let result = match &args.command {
+        Command::Check => check(&machines),
+        Command::Diagram(format) => emit(&machines, &args, |sm| {
+            Ok((format.extension(), diagram::generate(sm, format)?))
+        }),
+        Command::Export(ExportFormat::Json) => emit(&machines, &args, |sm| {
+            Ok(("json", export::generate_json(sm)))
+        }),
+        Command::Export(ExportFormat::Scxml) => emit(&machines, &args, |sm| {
+            Ok(("scxml", export::generate_scxml(sm)))
+        }),
+    };
+
+    match result {
+        Ok(true) if failures.is_empty() => ExitCode::SUCCESS,
+        Ok(_) => ExitCode::FAILURE,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// Prints the errors and warnings of all machines, returning if there were no errors.
+fn check(machines: &[Machine]) -> Result<bool, String> {
+    let mut error_count = 0;
+    let mut warning_count = 0;
+
+    for machine in machines {
+        match analyse(machine.tokens.clone()) {
+            Ok((_, warnings)) => {
+                for warning in warnings {
+                    warning_count += 1;
+                    println!(
+                        "{}: warning: {}",
+                        machine.location(warning.span),
+                        warning.message
+                    );
+                }
+            }
+            Err(errors) => {
+                for error in errors {
+                    error_count += 1;
+                    println!("{}: error: {}", machine.location(error.span()), error);
+                }
+            }
+        }
+    }
+
+    println!(
+        "checked {} state machine(s): {} error(s), {} warning(s)",
+        machines.len(),
+        error_count,
+        warning_count
+    );
+    Ok(error_count == 0)
+}
+
+/// Generates an output for every valid machine, printing it or writing it to the output
+/// directory. Invalid machines are reported as in `check`. Several JSON exports are printed as an
+/// array, so stdout holds a single JSON document.
+fn emit<F>(machines: &[Machine], args: &Args, generate: F) -> Result<bool, String>
+where
+    F: Fn(&parser::ParsedStateMachine) -> Result<(&'static str, String), String>,
+{
+    let mut success = true;
+    let json_array = matches!(args.command, Command::Export(ExportFormat::Json))
+        && args.output.is_none()
+        && machines.len() > 1;
+    let mut printed = Vec::new();
+
+    for machine in machines {
+        let sm = match analyse(machine.tokens.clone()) {
+            Ok((sm, _)) => sm,
+            Err(errors) => {
+                for error in errors {
+                    eprintln!("{}: error: {}", machine.location(error.span()), error);
+                }
+                success = false;
+                continue;
+            }
+        };
+
+        let (extension, output) = generate(&sm)?;
+        match &args.output {
+            Some(dir) => {
+                let name = sm
+                    .name
+                    .as_ref()
+                    .map_or_else(|| machine.fallback_name(), |name| name.to_string());
+                let path = dir.join(format!("statemachine_{}.{}", name, extension));
+                std::fs::write(&path, output)
+                    .map_err(|error| format!("failed to write `{}`: {}", path.display(), error))?;
+                eprintln!("wrote {}", path.display());
+            }
+            None if json_array => printed.push(output.trim_end().to_string()),
+            None => print!("{}", output),
+        }
+    }
+
+    if json_array {
+        println!("[\n{}\n]", printed.join(",\n"));
+    }
+
+    Ok(success)
+}
@@ -0,0 +1,170 @@
+use proc_macro2::{Span, TokenStream};
+use smlang_core::scxml::{self, Converted};
+use std::path::{Path, PathBuf};
+use syn::visit::Visit;
+
+/// The DSL of a state machine and where it was found.
+pub struct Machine {
+    pub file: PathBuf,
+    pub tokens: TokenStream,
+    /// The DSL converted from an SCXML document, which maps its lines to those of the document.
+    pub converted: Option<Converted>,
+}
+
+impl Machine {
+    /// Formats the location of a span in the DSL as `file:line:column`, or as `file:line` in an
+    /// SCXML document.
+    pub fn location(&self, span: Span) -> String {
+        let start = span.start();
+        match &self.converted {
+            Some(_) => format!("{}:{}", self.file.display(), self.line(span)),
+            None => format!(
+                "{}:{}:{}",
+                self.file.display(),
+                start.line,
+                start.column + 1
+            ),
+        }
+    }
+
+    // The line of a span in the file.
+    fn line(&self, span: Span) -> usize {
+        let line = span.start().line;
+        self.converted
+            .as_ref()
+            .map_or(line, |converted| converted.xml_line(line) as usize)
+    }
+
+    /// The name used for the output files of unnamed machines, based on the file and line.
+    pub fn fallback_name(&self) -> String {
+        let stem = self
+            .file
+            .file_stem()
+            .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+        let line = self
+            .tokens
+            .clone()
+            .into_iter()
+            .next()
+            .map_or(0, |token| self.line(token.span()));
+        format!("{}_{}", stem, line)
+    }
+}
+
+/// Loads the machines of a `.sm`, `.scxml` or `.rs` file, or of all such files in a directory. Files in a
+/// directory which cannot be read or parsed are added to `failures` and the scan continues with
+/// the other files.
+pub fn load(
+    path: &Path,
+    machines: &mut Vec<Machine>,
+    failures: &mut Vec<String>,
+) -> Result<(), String> {
+    if path.is_dir() {
+        let mut entries: Vec<PathBuf> = std::fs::read_dir(path)
+            .and_then(|entries| {
+                entries
+                    .map(|entry| entry.map(|entry| entry.path()))
+                    .collect()
+            })
+            .map_err(|error| format!("failed to read `{}`: {}", path.display(), error))?;
+        entries.sort();
+
+        for entry in entries {
+            // Skip build output and hidden directories such as `.git`
+            let name = entry.file_name().unwrap_or_default().to_string_lossy();
+            if name.starts_with('.') || name == "target" {
+                continue;
+            }
+            if entry.is_dir() || is_source(&entry) {
+                if let Err(error) = load(&entry, machines, failures) {
+                    failures.push(error);
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    let source = std::fs::read_to_string(path)
+        .map_err(|error| format!("failed to read `{}`: {}", path.display(), error))?;
+
+    if path.extension().is_some_and(|extension| extension == "rs") {
+        let file = syn::parse_file(&source).map_err(|error| {
+            let start = error.span().start();
+            format!(
+                "{}:{}:{}: {}",
+                path.display(),
+                start.line,
+                start.column + 1,
+                error
+            )
+        })?;
+        let mut finder = Finder {
+            file: path,
+            machines,
+        };
+        finder.visit_file(&file);
+    } else if path
+        .extension()
+        .is_some_and(|extension| extension == "scxml")
+    {
+        let converted = scxml::convert(&source)
+            .map_err(|error| format!("{}:{}: {}", path.display(), error.line, error.message))?;
+        let tokens = converted
+            .source
+            .parse()
+            .map_err(|error: proc_macro2::LexError| format!("{}: {}", path.display(), error))?;
+        machines.push(Machine {
+            file: path.to_path_buf(),
+            tokens,
+            converted: Some(converted),
+        });
+    } else {
+        let tokens = source.parse().map_err(|error: proc_macro2::LexError| {
+            let start = error.span().start();
+            format!(
+                "{}:{}:{}: {}",
+                path.display(),
+                start.line,
+                start.column + 1,
+                error
+            )
+        })?;
+        machines.push(Machine {
+            file: path.to_path_buf(),
+            tokens,
+            converted: None,
+        });
+    }
+
+    Ok(())
+}
+
+fn is_source(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == "rs" || extension == "sm" || extension == "scxml")
+}
+
+// Collects the contents of all `statemachine!` invocations. The files read by `statemachine_file!`
+// and `statemachine_scxml!` are not followed, they are loaded when passed or found in a directory.
+struct Finder<'a> {
+    file: &'a Path,
+    machines: &'a mut Vec<Machine>,
+}
+
+impl<'ast> Visit<'ast> for Finder<'_> {
+    fn visit_macro(&mut self, mac: &'ast syn::Macro) {
+        if mac
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "statemachine")
+        {
+            self.machines.push(Machine {
+                file: self.file.to_path_buf(),
+                tokens: mac.tokens.clone(),
+                converted: None,
+            });
+        }
+        syn::visit::visit_macro(self, mac);
+    }
+}
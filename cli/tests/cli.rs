@@ -0,0 +1,144 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn machine(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/machines")
+        .join(name)
+}
+
+fn smlang(args: &[&str], path: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_smlang"))
+        .args(args)
+        .arg(path)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn check_valid() {
+    let output = smlang(&["check"], &machine("door.rs"));
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "checked 1 state machine(s): 0 error(s), 0 warning(s)\n"
+    );
+}
+
+#[test]
+fn check_invalid() {
+    let path = machine("invalid.sm");
+    let output = smlang(&["check"], &path);
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with(&format!(
+        "{}:3:5: error: Idle + Start: State and event combination specified multiple times",
+        path.display()
+    )));
+}
+
+#[test]
+fn check_directory_with_unparsable_file() {
+    let dir = std::env::temp_dir().join("smlang-cli-unparsable");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("broken.rs"), "fn main( {}\n").unwrap();
+    std::fs::copy(machine("door.rs"), dir.join("door.rs")).unwrap();
+
+    let output = smlang(&["check"], &dir);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .starts_with(&format!("error: {}:1:", dir.join("broken.rs").display())));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "checked 1 state machine(s): 0 error(s), 0 warning(s)\n"
+    );
+}
+
+#[test]
+fn diagram_mermaid() {
+    let output = smlang(&["diagram", "--format", "mermaid"], &machine("door.rs"));
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "stateDiagram-v2
+    [*] --> Closed
+    Closed --> Opened : Open [unlocked]
+    Opened : entry / light_on
+    Opened --> Closed : Close
+"
+    );
+}
+
+#[test]
+fn diagram_mermaid_history() {
+    let output = smlang(&["diagram", "--format", "mermaid"], &machine("player.sm"));
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("    Interrupted --> H : Resume\n"));
+}
+
+#[test]
+fn check_scxml() {
+    let dir = std::env::temp_dir().join("smlang-cli-scxml");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("lamp.scxml");
+    std::fs::write(
+        &path,
+        r#"<scxml name="Lamp">
+    <state id="Off">
+        <transition event="Toggle" target="On"/>
+        <transition event="Toggle" target="On"/>
+    </state>
+    <state id="On">
+        <transition event="Toggle" target="Off"/>
+    </state>
+</scxml>
+"#,
+    )
+    .unwrap();
+
+    let output = smlang(&["check"], &dir);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .starts_with(&format!(
+            "{}:4: error: Off + Toggle: State and event combination specified multiple times",
+            path.display()
+        )));
+}
+
+#[test]
+fn export_several_to_stdout() {
+    let dir = std::env::temp_dir().join("smlang-cli-several");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::copy(machine("door.rs"), dir.join("door.rs")).unwrap();
+    std::fs::copy(machine("player.sm"), dir.join("player.sm")).unwrap();
+
+    let output = smlang(&["export", "--json"], &dir);
+    assert!(output.status.success());
+    let json = String::from_utf8(output.stdout).unwrap();
+    assert!(json.starts_with("[\n{\n  \"name\": \"Door\","));
+    assert!(json.contains("\n},\n{\n  \"name\": \"Player\","));
+    assert!(json.ends_with("\n}\n]\n"));
+
+    let output = smlang(&["export", "--scxml"], &dir);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn export_json_to_directory() {
+    let dir = std::env::temp_dir().join("smlang-cli-export");
+    std::fs::create_dir_all(&dir).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_smlang"))
+        .args(["export", "--json", "--output"])
+        .arg(&dir)
+        .arg(machine("../../../tests/machines/turnstile.sm"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let json = std::fs::read_to_string(dir.join("statemachine_Turnstile.json")).unwrap();
+    assert!(json.contains("\"starting_state\": \"Locked\""));
+}
@@ -0,0 +1,12 @@
+use smlang::statemachine;
+
+statemachine! {
+    name: Door,
+    states: {
+        Opened { entry / light_on },
+    },
+    transitions: {
+        *Closed + Open [unlocked] = Opened,
+        Opened + Close = Closed,
+    }
+}
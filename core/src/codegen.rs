@@ -0,0 +1,2008 @@
+// Move guards to return a Result
+
+use crate::parser::data::DataDefinitions;
+use crate::parser::output_state::HISTORY_STATE;
+use crate::parser::state_machine::ActionFailure;
+use crate::parser::transition::visit_guards;
+use crate::parser::{lifetimes::Lifetimes, AsyncIdent, ParsedStateMachine};
+use proc_macro2::{Ident, Span, TokenStream, TokenTree};
+use quote::{format_ident, quote};
+use syn::{GenericParam, Type};
+
+pub fn generate_code(sm: &ParsedStateMachine) -> proc_macro2::TokenStream {
+    let (sm_name, sm_name_span) = sm
+        .name
+        .as_ref()
+        .map(|name| (name.to_string(), name.span()))
+        .unwrap_or_else(|| (String::new(), Span::call_site()));
+    let states_type_name = format_ident!("{sm_name}States", span = sm_name_span);
+    let events_type_name = format_ident!("{sm_name}Events", span = sm_name_span);
+    let error_type_name = format_ident!("{sm_name}Error", span = sm_name_span);
+    let state_kind_type_name = format_ident!("{sm_name}StateKind", span = sm_name_span);
+    let event_kind_type_name = format_ident!("{sm_name}EventKind", span = sm_name_span);
+    let state_machine_type_name = format_ident!("{sm_name}StateMachine", span = sm_name_span);
+    let state_machine_context_type_name =
+        format_ident!("{sm_name}StateMachineContext", span = sm_name_span);
+
+    // The context and the state machine have all generic parameters, the states and events only
+    // those used by their data
+    let (generic_params, generic_args) = generic_parameters(sm, |_| true);
+    let (state_generic_params, state_generic_args) = generic_parameters(sm, |param| {
+        sm.state_data
+            .data_types
+            .values()
+            .any(|data_type| uses_ident(quote! { #data_type }, param))
+    });
+    let (event_generic_params, event_generic_args) = generic_parameters(sm, |param| {
+        sm.event_data
+            .data_types
+            .values()
+            .any(|data_type| uses_ident(quote! { #data_type }, param))
+    });
+
+    // The type parameter of the context, which must not clash with the declared parameters. When
+    // all of the readable names are declared, a name which is reserved for generated code is used.
+    let context_type = ["T", "C", "Context"]
+        .iter()
+        .map(|name| Ident::new(name, Span::call_site()))
+        .find(|name| {
+            !sm.generics
+                .params
+                .iter()
+                .any(|param| generic_param_ident(param) == Some(name))
+        })
+        .unwrap_or_else(|| Ident::new("__SmlangContext", Span::call_site()));
+
+    // Type parameters only used by the context need a marker in the state machine
+    let type_params: Vec<_> = sm
+        .generics
+        .type_params()
+        .map(|param| &param.ident)
+        .collect();
+    let (generics_field, generics_init) = if type_params.is_empty() {
+        (quote! {}, quote! {})
+    } else {
+        (
+            quote! { _generics: core::marker::PhantomData<fn() -> (#(#type_params,)*)>, },
+            quote! { _generics: core::marker::PhantomData, },
+        )
+    };
+
+    // Get only the unique states
+    let mut state_list: Vec<_> = sm.states.values().collect();
+    state_list.sort_by_key(|state| state.to_string());
+
+    let state_list: Vec<_> = state_list
+        .iter()
+        .map(
+            |value| match sm.state_data.data_types.get(&value.to_string()) {
+                None => {
+                    quote! {
+                        #value
+                    }
+                }
+                Some(t) => {
+                    quote! {
+                        #value(#t)
+                    }
+                }
+            },
+        )
+        .collect();
+
+    // Extract events
+    let mut event_list: Vec<_> = sm.events.values().collect();
+    event_list.sort_by_key(|event| event.to_string());
+
+    // Extract events
+    let event_list: Vec<_> = event_list
+        .iter()
+        .map(
+            |value| match sm.event_data.data_types.get(&value.to_string()) {
+                None => {
+                    quote! {
+                        #value
+                    }
+                }
+                Some(t) => {
+                    quote! {
+                        #value(#t)
+                    }
+                }
+            },
+        )
+        .collect();
+
+    let transitions = &sm.states_events_mapping;
+
+    let in_states: Vec<_> = transitions
+        .keys()
+        .map(|name| {
+            let state_name = sm.states.get(name).unwrap();
+
+            match sm.state_data.data_types.get(name) {
+                None => {
+                    quote! {
+                        #state_name
+                    }
+                }
+                Some(_) => {
+                    quote! {
+                        #state_name(ref state_data)
+                    }
+                }
+            }
+        })
+        .collect();
+
+    let events: Vec<Vec<_>> = transitions
+        .values()
+        .map(|value| {
+            value
+                .iter()
+                .map(|(name, value)| {
+                    let value = &value.event;
+
+                    match sm.event_data.data_types.get(name) {
+                        None => {
+                            quote! {
+                                #value
+                            }
+                        }
+                        Some(_) => {
+                            quote! {
+                                #value(event_data)
+                            }
+                        }
+                    }
+                })
+                .collect()
+        })
+        .collect();
+
+    // Map guards, actions and output states into code blocks
+    let guards: Vec<Vec<_>> = transitions
+        .values()
+        .map(|event_mappings| {
+            event_mappings
+                .values()
+                .map(|event_mapping| {
+                    event_mapping
+                        .transitions
+                        .iter()
+                        .map(|transition| transition.guard.clone())
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    let actions: Vec<Vec<_>> = transitions
+        .values()
+        .map(|event_mappings| {
+            event_mappings
+                .values()
+                .map(|event_mapping| {
+                    event_mapping
+                        .transitions
+                        .iter()
+                        .map(|transition| transition.action.clone())
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    let action_parameters: Vec<Vec<_>> = transitions
+        .iter()
+        .map(|(name, value)| {
+            let state_name = &sm.states.get(name).unwrap().to_string();
+
+            value
+                .keys()
+                .map(|name| {
+                    let state_data = match sm.state_data.data_types.get(state_name) {
+                        Some(Type::Reference(_)) => quote! { state_data },
+                        Some(_) => quote! { &state_data },
+                        None => quote! {},
+                    };
+
+                    let event_data = match sm.event_data.data_types.get(name) {
+                        Some(_) => quote! { event_data },
+                        None => quote! {},
+                    };
+
+                    if state_data.is_empty() || event_data.is_empty() {
+                        quote! { #state_data #event_data }
+                    } else {
+                        quote! { #state_data, #event_data }
+                    }
+                })
+                .collect()
+        })
+        .collect();
+
+    let guard_parameters: Vec<Vec<_>> = transitions
+        .iter()
+        .map(|(name, value)| {
+            let state_name = &sm.states.get(name).unwrap().to_string();
+
+            value
+                .keys()
+                .map(|name| {
+                    let state_data = match sm.state_data.data_types.get(state_name) {
+                        Some(Type::Reference(_)) => quote! { state_data },
+                        Some(_) => quote! { &state_data },
+                        None => quote! {},
+                    };
+
+                    let event_data = match sm.event_data.data_types.get(name) {
+                        Some(Type::Reference(_)) => quote! { event_data },
+                        Some(_) => quote! { &event_data },
+                        None => quote! {},
+                    };
+
+                    if state_data.is_empty() || event_data.is_empty() {
+                        quote! { #state_data #event_data }
+                    } else {
+                        quote! { #state_data, #event_data }
+                    }
+                })
+                .collect()
+        })
+        .collect();
+
+    // The errors of the transitions, which include the kinds of the state and event with detailed
+    // errors
+    let transition_errors: Vec<Vec<_>> = transitions
+        .iter()
+        .map(|(state, event_mappings)| {
+            let state = &sm.states[state];
+            event_mappings
+                .values()
+                .map(|event_mapping| {
+                    let event = &event_mapping.event;
+                    let error_states = event_mapping
+                        .transitions
+                        .iter()
+                        .map(|transition| transition.error_state.as_ref().or(sm.on_error.as_ref()).cloned())
+                        .collect();
+                    if sm.detailed_errors {
+                        let kinds = quote! {
+                            state: #state_kind_type_name::#state,
+                            event: #event_kind_type_name::#event,
+                        };
+                        TransitionErrors {
+                            action_failed: quote! { |error| #error_type_name::ActionFailed { #kinds error } },
+                            transitions_failed: quote! { #error_type_name::TransitionsFailed { #kinds } },
+                            kinds,
+                            error_states,
+                        }
+                    } else {
+                        TransitionErrors {
+                            action_failed: quote! { #error_type_name::ActionFailed },
+                            transitions_failed: quote! { #error_type_name::TransitionsFailed },
+                            kinds: quote! {},
+                            error_states,
+                        }
+                    }
+                })
+                .collect()
+        })
+        .collect();
+    let transitions_failed: Vec<Vec<_>> = transition_errors
+        .iter()
+        .map(|errors| {
+            errors
+                .iter()
+                .map(|errors| &errors.transitions_failed)
+                .collect()
+        })
+        .collect();
+
+    // With detailed errors an invalid event is handed back
+    let invalid_event: Vec<_> = transitions
+        .keys()
+        .map(|state| {
+            let state = &sm.states[state];
+            if sm.detailed_errors {
+                quote! { #error_type_name::InvalidEvent { state: #state_kind_type_name::#state, event } }
+            } else {
+                quote! { #error_type_name::InvalidEvent }
+            }
+        })
+        .collect();
+
+    let custom_error = if sm.custom_error {
+        quote! { Self::Error }
+    } else {
+        quote! { () }
+    };
+
+    let out_states: Vec<Vec<Vec<TokenStream>>> = transitions
+        .values()
+        .map(|event_mappings| {
+            event_mappings
+                .values()
+                .map(|event_mapping| {
+                    event_mapping
+                        .transitions
+                        .iter()
+                        .map(|transition| transition.out_state.clone())
+                        .map(|out_state| {
+                            match sm.state_data.data_types.get(&out_state.to_string()) {
+                                None => {
+                                    quote! {
+                                        #out_state
+                                    }
+                                }
+                                Some(_) => {
+                                    quote! {
+                                        #out_state(_data)
+                                    }
+                                }
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    let temporary_context = match &sm.temporary_context_type {
+        Some(tct) => {
+            quote! { temporary_context: #tct, }
+        }
+        None => {
+            quote! {}
+        }
+    };
+
+    // Keep track of already added actions not to duplicate definitions
+    let mut action_set: Vec<syn::Ident> = Vec::new();
+    let mut guard_set: Vec<syn::Ident> = Vec::new();
+
+    let mut guard_list = proc_macro2::TokenStream::new();
+    let mut action_list = proc_macro2::TokenStream::new();
+
+    let mut entries_exits = proc_macro2::TokenStream::new();
+
+    for (state, event_mappings) in transitions.iter() {
+        // create the state data token stream
+        let state_data = match sm.state_data.data_types.get(state) {
+            Some(st @ Type::Reference(_)) => quote! { state_data: #st, },
+            Some(st) => quote! { state_data: &#st, },
+            None => quote! {},
+        };
+
+        let entry_ident = format_ident!("on_entry_{}", string_morph::to_snake_case(state));
+        let state_name = format!("[{}::{}]", states_type_name, state);
+        let (entry_exit_async, entry_exit_return) =
+            async_signature(sm.entry_exit_async, sm.async_send, None);
+        let entry_exit_body = if sm.entry_exit_async && sm.async_send {
+            quote! { { async {} } }
+        } else {
+            quote! { {} }
+        };
+        entries_exits.extend(quote! {
+            #[doc = concat!("Called on entry to ", #state_name)]
+            #[inline(always)]
+            #[allow(clippy::manual_async_fn)]
+            #entry_exit_async fn #entry_ident(&mut self) #entry_exit_return #entry_exit_body
+        });
+        let exit_ident = format_ident!("on_exit_{}", string_morph::to_snake_case(state));
+        entries_exits.extend(quote! {
+            #[doc = concat!("Called on exit from ", #state_name)]
+            #[inline(always)]
+            #[allow(clippy::manual_async_fn)]
+            #entry_exit_async fn #exit_ident(&mut self) #entry_exit_return #entry_exit_body
+        });
+
+        // Create the entry and exit actions declared for this state, these have no default
+        // implementation
+        if let Some(declaration) = sm.state_declarations.get(state) {
+            for (kind, action) in [
+                ("entry to ", &declaration.entry),
+                ("exit from ", &declaration.exit),
+            ] {
+                if let Some(AsyncIdent {
+                    ident: action,
+                    is_async,
+                    ..
+                }) = action
+                {
+                    if !action_set.iter().any(|a| a == action) {
+                        action_set.push(action.clone());
+                        let (is_async, return_type) =
+                            async_signature(*is_async, sm.async_send, None);
+                        let doc = format!("Called on {}{}", kind, state_name);
+                        action_list.extend(quote! {
+                            #[doc = #doc]
+                            #is_async fn #action(&mut self) #return_type;
+                        });
+                    }
+                }
+            }
+
+            if let Some(AsyncIdent {
+                ident: activity, ..
+            }) = &declaration.activity
+            {
+                if !action_set.iter().any(|a| a == activity) {
+                    action_set.push(activity.clone());
+                    let event_lifetimes = &sm.event_data.all_lifetimes;
+                    let doc = format!("Activity running while in {}", state_name);
+                    // The future must not borrow the context, which processes events while the
+                    // activity is pending
+                    let lifetime_bounds: Vec<_> = if event_lifetimes.is_empty() {
+                        vec![quote! { 'static }]
+                    } else {
+                        event_lifetimes
+                            .as_slice()
+                            .iter()
+                            .map(|lifetime| quote! { #lifetime })
+                            .collect()
+                    };
+                    let send = if sm.async_send {
+                        quote! { + Send }
+                    } else {
+                        quote! {}
+                    };
+                    let event_type_params = sm.generics.type_params().filter(|param| {
+                        let param = &param.ident;
+                        sm.event_data
+                            .data_types
+                            .values()
+                            .any(|data_type| uses_ident(quote! { #data_type }, param))
+                    });
+                    let type_param_bounds = event_type_params.map(|param| {
+                        let param = &param.ident;
+                        quote! { #param: #(#lifetime_bounds)+*, }
+                    });
+                    action_list.extend(quote! {
+                        #[doc = #doc]
+                        ///
+                        /// Returning `Some(event)` processes the event, returning `None` ends the
+                        /// activity until the state is exited. The future keeps running while
+                        /// events that do not leave the state are processed, so it can't borrow
+                        /// the context.
+                        fn #activity <#event_lifetimes> (&mut self)
+                            -> impl core::future::Future<Output = Option<#events_type_name <#event_lifetimes #event_generic_args>>> #send #(+ #lifetime_bounds)*
+                        where
+                            #(#type_param_bounds)*;
+                    });
+                }
+            }
+        }
+
+        for (event, event_mapping) in event_mappings {
+            for transition in &event_mapping.transitions {
+                // get input state lifetimes
+                let in_state_lifetimes = sm
+                    .state_data
+                    .lifetimes
+                    .get(&event_mapping.in_state.to_string())
+                    .cloned()
+                    .unwrap_or_default();
+
+                // get output state lifetimes
+                let out_state_lifetimes = sm
+                    .state_data
+                    .lifetimes
+                    .get(&transition.out_state.to_string())
+                    .cloned()
+                    .unwrap_or_default();
+
+                // get event lifetimes
+                let event_lifetimes = sm
+                    .event_data
+                    .lifetimes
+                    .get(event)
+                    .cloned()
+                    .unwrap_or_default();
+
+                // combine all lifetimes
+                let mut all_lifetimes = Lifetimes::new();
+                all_lifetimes.extend(&in_state_lifetimes);
+                all_lifetimes.extend(&out_state_lifetimes);
+                all_lifetimes.extend(&event_lifetimes);
+
+                // Create the guard traits for user implementation
+                if let Some(guard_expression) = &transition.guard {
+                    visit_guards(guard_expression,|guard| {
+                        let is_async = guard.is_async;
+                        let return_type = if guard.is_fallible(sm.fallible) {
+                            quote! { Result<bool,#custom_error> }
+                        } else {
+                            quote! { bool }
+                        };
+                        let guard = &guard.ident;
+                        let event_data = match sm.event_data.data_types.get(event) {
+                            Some(et @ Type::Reference(_)) => quote! { event_data: #et },
+                            Some(et) => quote! { event_data: &#et },
+                            None => quote! {},
+                        };
+
+                        // Only add the guard if it hasn't been added before
+                        if !guard_set.iter().any(|g| g == guard) {
+                            guard_set.push(guard.clone());
+                            let (is_async, return_type) = async_signature(is_async, sm.async_send, Some(return_type));
+                            guard_list.extend(quote! {
+                            #[allow(missing_docs)]
+                            #[allow(clippy::result_unit_err)]
+                            #is_async fn #guard <#all_lifetimes> (&self, #temporary_context #state_data #event_data) #return_type;
+                        });
+                        };
+                        Ok(())
+                    }).unwrap();
+                }
+
+                // Create the action traits for user implementation
+                if let Some(async_ident) = &transition.action {
+                    let action = &async_ident.ident;
+                    let output_data = sm
+                        .state_data
+                        .data_types
+                        .get(&transition.out_state.to_string());
+                    let return_type = match (async_ident.is_fallible(sm.fallible), output_data) {
+                        (true, Some(output_data)) => {
+                            Some(quote! { Result<#output_data,#custom_error> })
+                        }
+                        // Empty return type
+                        (true, None) => Some(quote! { Result<(),#custom_error> }),
+                        (false, Some(output_data)) => Some(quote! { #output_data }),
+                        (false, None) => None,
+                    };
+                    let (is_async, return_type) =
+                        async_signature(async_ident.is_async, sm.async_send, return_type);
+
+                    let event_data = match sm.event_data.data_types.get(event) {
+                        Some(et) => {
+                            quote! { event_data: #et }
+                        }
+                        None => {
+                            quote! {}
+                        }
+                    };
+
+                    // Only add the action if it hasn't been added before
+                    if !action_set.iter().any(|a| a == action) {
+                        action_set.push(action.clone());
+                        action_list.extend(quote! {
+                            #[allow(missing_docs)]
+                            #[allow(clippy::unused_unit)]
+                            #[allow(clippy::result_unit_err)]
+                            #is_async fn #action <#all_lifetimes> (&mut self, #temporary_context #state_data #event_data) #return_type;
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    let temporary_context_call = match &sm.temporary_context_type {
+        Some(_) => {
+            quote! { temporary_context, }
+        }
+        None => {
+            quote! {}
+        }
+    };
+
+    let mut is_async_state_machine = sm.entry_exit_async;
+
+    let entry_exit_await = if sm.entry_exit_async {
+        quote! { .await }
+    } else {
+        quote! {}
+    };
+
+    // When the history state is used, every transition to a new state stores the exited state
+    let has_history = sm.has_history();
+    let store_state = if has_history {
+        quote! { self.history = Some(core::mem::replace(&mut self.state, out_state)); }
+    } else {
+        quote! { self.state = out_state; }
+    };
+
+    // The state entered through the history state is only known at runtime, so the entry
+    // functions are dispatched on the restored state
+    let mut is_async_history_entry = sm.entry_exit_async;
+    let history_entry_arms: Vec<_> = {
+        let mut states: Vec<_> = sm.states.values().collect();
+        states.sort_by_key(|state| state.to_string());
+        states
+            .into_iter()
+            .map(|state| {
+                let state_name = state.to_string();
+                let entry_ident =
+                    format_ident!("on_entry_{}", string_morph::to_snake_case(&state_name));
+                let (is_async_entry, entry_action_code) = generate_state_action(
+                    sm.state_declarations
+                        .get(&state_name)
+                        .and_then(|d| d.entry.as_ref()),
+                );
+                if has_history {
+                    is_async_state_machine |= is_async_entry;
+                }
+                is_async_history_entry |= is_async_entry;
+                let state_pattern = match sm.state_data.data_types.get(&state_name) {
+                    Some(_) => quote! { #state(..) },
+                    None => quote! { #state },
+                };
+                quote! {
+                    #states_type_name::#state_pattern => {
+                        self.context.#entry_ident()#entry_exit_await;
+                        #entry_action_code
+                    }
+                }
+            })
+            .collect()
+    };
+
+    // Create the code blocks inside the switch cases, where `mark_transition` is the code run once
+    // the guards of a transition passed, before its first exit function or action
+    let mut generate_code_blocks =
+        |mark_transition: &TokenStream| -> Vec<Vec<(TokenStream, bool)>> {
+            guards
+            .iter()
+            .zip(
+                actions
+                    .iter()
+                    .zip(in_states.iter().zip(out_states.iter().zip(action_parameters.iter().zip(guard_parameters.iter().zip(transition_errors.iter()))))),
+            )
+            .map(
+                |(guards, (actions, (in_state, (out_states, (action_parameters, (guard_parameters, transition_errors))))))| {
+                    guards
+                        .iter()
+                        .zip(
+                            actions
+                                .iter()
+                                .zip(out_states.iter().zip(action_parameters.iter().zip(guard_parameters.iter().zip(transition_errors.iter())))),
+                        )
+                        .map(|(guard, (action, (out_state, (action_params, (guard_params, errors)))))| {
+                            // Whether processing the event in the state may await anything
+                            let mut is_async_block = false;
+                            let streams: Vec<TokenStream> =
+                                guard.iter()
+                                .zip(action.iter().zip(out_state.iter().zip(&errors.error_states))).map(|(guard, (action, (out_state, error_state)))| {
+                                    let binding = out_state.to_string();
+                                    let out_state_string = binding.split('(').next().unwrap().trim();
+                                    let binding = in_state.to_string();
+                                    let in_state_string = binding.split('(').next().unwrap().trim();
+
+                                    let entry_ident = format_ident!("on_entry_{}", string_morph::to_snake_case(out_state_string));
+                                    let exit_ident = format_ident!("on_exit_{}", string_morph::to_snake_case(in_state_string));
+                                    let (is_async_entry, entry_action_code) = generate_state_action(
+                                        sm.state_declarations.get(out_state_string).and_then(|d| d.entry.as_ref()),
+                                    );
+                                    let (is_async_exit, exit_action_code) = generate_state_action(
+                                        sm.state_declarations.get(in_state_string).and_then(|d| d.exit.as_ref()),
+                                    );
+                                    let is_async_entry = is_async_entry || sm.entry_exit_async;
+                                    let is_async_exit = is_async_exit || sm.entry_exit_async;
+
+                                    // Enters the error state with the error of the guard or action, which is
+                                    // converted into the data of the state
+                                    let enter_error_state = |error_state: &Ident, exit: bool| {
+                                        let (is_async_error_entry, error_entry_code) = generate_state_entry(sm, &error_state.to_string(), &entry_exit_await);
+                                        let (binding, value) = if sm.state_data.data_types.contains_key(&error_state.to_string()) {
+                                            (quote! { error }, quote! { #error_state(error.into()) })
+                                        } else {
+                                            (quote! { _ }, quote! { #error_state })
+                                        };
+                                        let exit_code = if exit {
+                                            quote! {
+                                                self.context.#exit_ident()#entry_exit_await;
+                                                #exit_action_code
+                                            }
+                                        } else {
+                                            quote! {}
+                                        };
+                                        let code = quote! {
+                                            Err(#binding) => {
+                                                #mark_transition
+                                                #exit_code
+                                                let out_state = #states_type_name::#value;
+                                                self.context().transition_callback(&self.state, &out_state);
+                                                #store_state
+                                                #error_entry_code
+                                                return Ok(&self.state);
+                                            }
+                                        };
+                                        (is_async_error_entry || (exit && is_async_exit), code)
+                                    };
+
+                                    // The code run when the action fails, which returns from `process_event`
+                                    let internal = in_state_string == out_state_string;
+                                    let action_failed = &errors.action_failed;
+                                    let on_failure = match (error_state, &sm.action_failure) {
+                                        (Some(error_state), action_failure) => {
+                                            // The exit hooks have run unless the state is not left or the
+                                            // action runs first
+                                            let exit = internal || *action_failure == ActionFailure::ActionFirst;
+                                            let (is_async_error_state, code) = enter_error_state(error_state, exit);
+                                            is_async_block |= is_async_error_state;
+                                            Some(code)
+                                        }
+                                        (None, ActionFailure::Reenter) if !internal => {
+                                            let (is_async_reentry, reentry_code) = generate_state_entry(sm, in_state_string, &entry_exit_await);
+                                            is_async_block |= is_async_reentry;
+                                            Some(quote! {
+                                                Err(error) => {
+                                                    #reentry_code
+                                                    return Err((#action_failed)(error));
+                                                }
+                                            })
+                                        }
+                                        (None, ActionFailure::Enter(failure_state)) => {
+                                            let (is_async_failure_entry, failure_entry_code) = generate_state_entry(sm, &failure_state.to_string(), &entry_exit_await);
+                                            is_async_block |= is_async_failure_entry;
+                                            // Internal transitions have not exited the state yet
+                                            let exit_code = if internal {
+                                                is_async_block |= is_async_exit;
+                                                quote! {
+                                                    self.context.#exit_ident()#entry_exit_await;
+                                                    #exit_action_code
+                                                }
+                                            } else {
+                                                quote! {}
+                                            };
+                                            Some(quote! {
+                                                Err(error) => {
+                                                    #exit_code
+                                                    let out_state = #states_type_name::#failure_state;
+                                                    self.context().transition_callback(&self.state, &out_state);
+                                                    #store_state
+                                                    #failure_entry_code
+                                                    return Err((#action_failed)(error));
+                                                }
+                                            })
+                                        }
+                                        _ => None,
+                                    };
+
+                                    let (is_async_action, action_code) = generate_action(action, &temporary_context_call, action_params, action_failed, sm.fallible, on_failure);
+                                    is_async_block |= is_async_action;
+
+                                    // The action runs either before or after the exit hooks
+                                    let (action_before_exit, action_after_exit) = if sm.action_failure == ActionFailure::ActionFirst {
+                                        (action_code, quote! {})
+                                    } else {
+                                        (quote! {}, action_code)
+                                    };
+
+                                    let transition = if out_state_string == HISTORY_STATE {
+                                        // Return to the most recently exited state
+                                        is_async_block |= is_async_exit | is_async_history_entry;
+                                        quote!{
+                                                if self.history.is_none() {
+                                                    return Err(#error_type_name::NoHistory);
+                                                }
+                                                #mark_transition
+                                                #action_before_exit
+                                                self.context.#exit_ident()#entry_exit_await;
+                                                #exit_action_code
+                                                #action_after_exit
+                                                let out_state = match self.history.take() {
+                                                    Some(out_state) => out_state,
+                                                    None => return Err(#error_type_name::NoHistory),
+                                                };
+                                                self.context().transition_callback(&self.state, &out_state);
+                                                #store_state
+                                                match self.state {
+                                                    #(#history_entry_arms)*
+                                                }
+                                                return Ok(&self.state);
+                                            }
+                                    } else if in_state_string == out_state_string {
+                                        // Stay in the same state => no need to call on_entry/on_exit
+                                        quote!{
+                                                #mark_transition
+                                                #action_before_exit
+                                                #action_after_exit
+                                                self.state = #states_type_name::#out_state;
+                                                return Ok(&self.state);
+                                            }
+                                    } else {
+                                        is_async_block |= is_async_entry | is_async_exit;
+                                        quote!{
+                                                #mark_transition
+                                                #action_before_exit
+                                                self.context.#exit_ident()#entry_exit_await;
+                                                #exit_action_code
+                                                #action_after_exit
+                                                let out_state = #states_type_name::#out_state;
+                                                self.context().transition_callback(&self.state, &out_state);
+                                                #store_state
+                                                self.context.#entry_ident()#entry_exit_await;
+                                                #entry_action_code
+                                                return Ok(&self.state);
+                                            }
+                                    };
+                                    if let Some(expr) = guard { // Guarded transition
+                                        let kinds = &errors.kinds;
+                                        // A failed guard leaves the state before entering the error state
+                                        let guard_failure = error_state.as_ref().map(|error_state| {
+                                            let (is_async_error_state, code) = enter_error_state(error_state, true);
+                                            is_async_block |= is_async_error_state;
+                                            code
+                                        });
+                                        let guard_expression= expr.to_token_stream(&mut |async_ident: &AsyncIdent| {
+                                            let guard_ident = &async_ident.ident;
+                                            let guard_await = if async_ident.is_async {
+                                                is_async_block = true;
+                                                quote! { .await }
+                                            } else {
+                                                quote! {}
+                                            };
+                                            if let (true, Some(guard_failure)) = (async_ident.is_fallible(sm.fallible), &guard_failure) {
+                                                quote! {
+                                                    {
+                                                        let result = self.context.#guard_ident(#temporary_context_call #guard_params) #guard_await;
+                                                        self.context.log_guard_result(stringify!(#guard_ident), &result);
+                                                        match result {
+                                                            Ok(passed) => passed,
+                                                            #guard_failure
+                                                        }
+                                                    }
+                                                }
+                                            } else if async_ident.is_fallible(sm.fallible) {
+                                                quote! {
+                                                    {
+                                                        let result = self.context.#guard_ident(#temporary_context_call #guard_params) #guard_await;
+                                                        self.context.log_guard_result(stringify!(#guard_ident), &result);
+                                                        result.map_err(|error| #error_type_name::GuardFailed {
+                                                            #kinds
+                                                            guard: stringify!(#guard_ident),
+                                                            error,
+                                                        })?
+                                                    }
+                                                }
+                                            } else {
+                                                quote! {
+                                                    {
+                                                        let result = self.context.#guard_ident(#temporary_context_call #guard_params) #guard_await;
+                                                        self.context.log_guard_result(stringify!(#guard_ident), &Ok(result));
+                                                        result
+                                                    }
+                                                }
+                                            }
+                                        });
+                                        let guard_string = crate::export::guard_string(expr);
+                                        quote! {
+                                            // This #guard_expression contains a boolean expression of guard functions
+                                            // Each guard function has Result<bool,_> return type.
+                                            // For example, [ f && !g ] will expand into
+                                            //  self.context.f()? && !self.context.g()?
+                                            // where every guard call is logged and its error records the guard.
+                                            let guard_passed = #guard_expression;
+                                            self.context.log_guard(#guard_string, guard_passed);
+
+                                            // If the guard passed, we transition immediately.
+                                            // Otherwise, there may be a later transition that passes,
+                                            // so we'll defer to that.
+                                            if guard_passed {
+                                                #transition
+                                            }
+                                        }
+                                    } else { // Unguarded transition
+                                       quote!{
+                                            #transition
+                                       }
+                                    }
+                                }
+                                ).collect();
+                            is_async_state_machine |= is_async_block;
+                            let code = quote!{
+                                #(#streams)*
+                            };
+                            (code, is_async_block)
+                        })
+                        .collect()
+                },
+            )
+            .collect()
+        };
+
+    // When processing an event awaits something, the future of `process_event()` may be dropped
+    // in the middle of a transition, which is recorded until the machine is recovered
+    let mut code_blocks = generate_code_blocks(&quote! {});
+    let is_cancellable = code_blocks
+        .iter()
+        .flatten()
+        .any(|(_, requires_async)| *requires_async);
+    if is_cancellable {
+        code_blocks = generate_code_blocks(&quote! { self.in_transition = true; });
+    }
+
+    let (code_blocks, requires_async): (Vec<Vec<_>>, Vec<Vec<_>>) = code_blocks
+        .into_iter()
+        .map(|blocks| blocks.into_iter().unzip())
+        .unzip();
+
+    let state_lifetimes = &sm.state_data.all_lifetimes;
+    let event_lifetimes = &sm.event_data.all_lifetimes;
+
+    let (in_transition_field, in_transition_init, in_transition_code, in_transition_error) =
+        if is_cancellable {
+            (
+                quote! { in_transition: bool, },
+                quote! { in_transition: false, },
+                quote! {
+                    /// Returns `true` if the future of a `process_event()` call was dropped before
+                    /// the transition finished, e.g. when it lost a `select!`.
+                    ///
+                    /// Some exit and entry functions may have run without the others, and the state
+                    /// may be the one that was left or the one that was entered. No more events are
+                    /// processed until the machine is recovered with `recover()` or
+                    /// `recover_with_state()`.
+                    #[inline(always)]
+                    pub fn is_in_transition(&self) -> bool {
+                        self.in_transition
+                    }
+
+                    /// Processes events again after an interrupted transition, in the current state.
+                    #[inline(always)]
+                    pub fn recover(&mut self) {
+                        self.in_transition = false;
+                    }
+
+                    /// Processes events again after an interrupted transition, in the given state.
+                    /// No entry functions are called.
+                    #[inline(always)]
+                    pub fn recover_with_state(&mut self, state: #states_type_name <#state_lifetimes #state_generic_args>) {
+                        self.state = state;
+                        self.in_transition = false;
+                    }
+                },
+                quote! {
+                    /// When an event is processed after the future of a previous `process_event()`
+                    /// call was dropped before its transition finished.
+                    InTransition,
+                },
+            )
+        } else {
+            (quote! {}, quote! {}, quote! {}, quote! {})
+        };
+
+    let (history_field, history_init, history_code, history_error) = if has_history {
+        (
+            quote! { history: Option<#states_type_name <#state_lifetimes #state_generic_args>>, },
+            quote! { history: None, },
+            quote! {
+                /// Creates a new state machine with an initial state and history, e.g. to restore a
+                /// previously taken snapshot of `state()` and `history()`.
+                #[inline(always)]
+                pub const fn new_with_history(
+                    context: #context_type,
+                    initial_state: #states_type_name <#state_lifetimes #state_generic_args>,
+                    history: Option<#states_type_name <#state_lifetimes #state_generic_args>>,
+                ) -> Self {
+                    #state_machine_type_name {
+                        state: initial_state,
+                        history,
+                        #in_transition_init
+                        #generics_init
+                        context
+                    }
+                }
+
+                /// Returns the most recently exited state, which is entered when transitioning to
+                /// the history state `H`.
+                #[inline(always)]
+                pub fn history(&self) -> Option<&#states_type_name <#state_lifetimes #state_generic_args>> {
+                    self.history.as_ref()
+                }
+            },
+            quote! {
+                /// When transitioning to the history state before any state was exited.
+                NoHistory,
+            },
+        )
+    } else {
+        (quote! {}, quote! {}, quote! {}, quote! {})
+    };
+
+    // Final states do not process any events
+    let mut final_states: Vec<_> = sm
+        .state_declarations
+        .values()
+        .filter(|declaration| declaration.is_final)
+        .map(
+            |declaration| match sm.state_data.data_types.get(&declaration.ident.to_string()) {
+                Some(_) => {
+                    let state = &declaration.ident;
+                    quote! { #state(..) }
+                }
+                None => {
+                    let state = &declaration.ident;
+                    quote! { #state }
+                }
+            },
+        )
+        .collect();
+    final_states.sort_by_key(|state| state.to_string());
+
+    let (is_finished_code, finished_check, finished_error) = if final_states.is_empty() {
+        (quote! { false }, quote! {}, quote! {})
+    } else {
+        (
+            quote! { matches!(self.state, #(#states_type_name::#final_states)|*) },
+            quote! {
+                if self.is_finished() {
+                    return Err(#error_type_name::MachineFinished);
+                }
+            },
+            quote! {
+                /// When an event is processed after the machine reached a final state.
+                MachineFinished,
+            },
+        )
+    };
+
+    let starting_state = &sm.starting_state;
+
+    // create a token stream for creating a new machine.  If the starting state contains data, then
+    // add a second argument to pass this initial data
+    let starting_state_name = starting_state.to_string();
+    let new_sm_code = match sm.state_data.data_types.get(&starting_state_name) {
+        Some(st) => quote! {
+            pub const fn new(context: #context_type, state_data: #st ) -> Self {
+                #state_machine_type_name {
+                    state: #states_type_name::#starting_state (state_data),
+                    #history_init
+                    #in_transition_init
+                    #generics_init
+                    context
+                }
+            }
+        },
+        None => quote! {
+            pub const fn new(context: #context_type ) -> Self {
+                #state_machine_type_name {
+                    state: #states_type_name::#starting_state,
+                    #history_init
+                    #in_transition_init
+                    #generics_init
+                    context
+                }
+            }
+        },
+    };
+
+    // lifetimes that exists in #events_type_name but not in #states_type_name
+    let event_unique_lifetimes = event_lifetimes - state_lifetimes;
+
+    let guard_error = if sm.custom_error {
+        quote! { Self::Error }
+    } else {
+        quote! { () }
+    };
+
+    let custom_error = if sm.custom_error {
+        quote! {
+            /// The error type returned by guard or action functions.
+            type Error: core::fmt::Debug;
+        }
+    } else {
+        quote! {}
+    };
+
+    // With detailed errors the error has the lifetimes and generic parameters of the events
+    let error_event_args = if sm.detailed_errors {
+        quote! { #event_lifetimes #event_generic_args }
+    } else {
+        quote! {}
+    };
+    // The data of the error states is converted from the errors of guards and actions
+    let mut error_state_data: Vec<_> = sm
+        .on_error
+        .iter()
+        .chain(
+            sm.states_events_mapping
+                .values()
+                .flat_map(|event_mappings| event_mappings.values())
+                .flat_map(|event_mapping| &event_mapping.transitions)
+                .filter_map(|transition| transition.error_state.as_ref()),
+        )
+        .filter_map(|state| sm.state_data.data_types.get(&state.to_string()))
+        .map(|data_type| quote! { #data_type })
+        .collect();
+    error_state_data.sort_by_key(|data_type| data_type.to_string());
+    error_state_data.dedup_by_key(|data_type| data_type.to_string());
+    let failure_error = if sm.custom_error {
+        quote! { <#context_type as #state_machine_context_type_name<#generic_args>>::Error }
+    } else {
+        quote! { () }
+    };
+    let error_state_bounds: Vec<_> = error_state_data
+        .iter()
+        .map(|data_type| quote! { #data_type: From<#failure_error> })
+        .collect();
+
+    let error_type = if sm.custom_error {
+        quote! {
+            #error_type_name<#error_event_args <#context_type as #state_machine_context_type_name<#generic_args>>::Error>
+        }
+    } else {
+        quote! {#error_type_name<#error_event_args>}
+    };
+
+    // Create the code driving the do-activities of the states
+    let mut activity_states: Vec<_> = sm
+        .state_declarations
+        .values()
+        .filter_map(|declaration| {
+            declaration
+                .activity
+                .as_ref()
+                .map(|activity| (&declaration.ident, &activity.ident))
+        })
+        .collect();
+    activity_states.sort_by_key(|(state, _)| state.to_string());
+
+    // The futures returned by the context are `Send`, so the futures of the state machine are too
+    // when it can be sent between threads. Only the methods processing events convert errors to
+    // the data of error states, so the other methods are available without the conversions.
+    let mut process_event_bounds = error_state_bounds.clone();
+    let mut run_bounds = error_state_bounds;
+    if sm.async_send {
+        process_event_bounds.push(quote! { Self: Send });
+        run_bounds.extend([quote! { Self: Send }, quote! { S: Send }]);
+    }
+    let process_event_bounds = if process_event_bounds.is_empty() {
+        quote! {}
+    } else {
+        quote! { where #(#process_event_bounds),* }
+    };
+
+    let run_code = if activity_states.is_empty() {
+        quote! {}
+    } else {
+        is_async_state_machine = true;
+
+        // Each activity is stored in its own slot, as their futures have different types
+        let activity_slots: Vec<_> = activity_states
+            .iter()
+            .map(|(state, _)| {
+                format_ident!(
+                    "activity_{}",
+                    string_morph::to_snake_case(&state.to_string())
+                )
+            })
+            .collect();
+        let activity_patterns: Vec<_> = activity_states
+            .iter()
+            .map(
+                |(state, _)| match sm.state_data.data_types.get(&state.to_string()) {
+                    Some(_) => quote! { #state(..) },
+                    None => quote! { #state },
+                },
+            )
+            .collect();
+        let activities = activity_states.iter().map(|(_, activity)| activity);
+
+        quote! {
+            /// Runs the state machine, processing the events of a stream while concurrently
+            /// driving the do-activity of the current state.
+            ///
+            /// The activity is started when the state is entered and keeps running while events
+            /// which do not leave the state are processed. It is dropped when the state is exited,
+            /// and started again when it returned an event and the machine is still in the state.
+            ///
+            /// Returns `Ok(())` once the stream ends, or the first error returned by
+            /// `process_event()`.
+            pub async fn run<#event_unique_lifetimes S>(&mut self, events: S) -> Result<(), #error_type>
+            where
+                S: ::smlang::Stream<Item = #events_type_name <#event_lifetimes #event_generic_args>>,
+                #(#run_bounds,)*
+            {
+                let mut events = core::pin::pin!(events);
+                #(let mut #activity_slots = core::pin::pin!(None);)*
+                let mut activity_finished = false;
+                loop {
+                    #[allow(unreachable_patterns)]
+                    match self.state {
+                        #(#states_type_name::#activity_patterns if !activity_finished && #activity_slots.is_none() => {
+                            #activity_slots.set(Some(self.context.#activities()));
+                        })*
+                        _ => {}
+                    }
+
+                    // `None` when the activity ended, `Some(None)` when the stream ended
+                    let next = core::future::poll_fn(|cx| {
+                        if let core::task::Poll::Ready(event) = ::smlang::Stream::poll_next(events.as_mut(), cx) {
+                            return core::task::Poll::Ready(Some(event));
+                        }
+                        #[allow(unreachable_patterns)]
+                        match self.state {
+                            #(#states_type_name::#activity_patterns => {
+                                if let Some(activity) = #activity_slots.as_mut().as_pin_mut() {
+                                    if let core::task::Poll::Ready(event) = core::future::Future::poll(activity, cx) {
+                                        #activity_slots.set(None);
+                                        return core::task::Poll::Ready(event.map(Some));
+                                    }
+                                }
+                            })*
+                            _ => {}
+                        }
+                        core::task::Poll::Pending
+                    }).await;
+
+                    match next {
+                        None => activity_finished = true,
+                        Some(None) => return Ok(()),
+                        Some(Some(event)) => {
+                            let state = core::mem::discriminant(&self.state);
+                            self.process_event(event).await?;
+                            // The activity is cancelled when the state is exited
+                            if core::mem::discriminant(&self.state) != state {
+                                #(#activity_slots.set(None);)*
+                                activity_finished = false;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    };
+
+    let is_async = if is_async_state_machine {
+        quote! { async }
+    } else {
+        quote! {}
+    };
+
+    let process_event_code = quote! {
+        self.context.log_process_event(self.state(), &event);
+        #finished_check
+        match self.state {
+            #(
+            #[allow(clippy::match_single_binding)]
+            #states_type_name::#in_states => match event {
+                #(#events_type_name::#events => {
+                    #code_blocks
+
+                    #[allow(unreachable_code)]
+                    {
+                        // none of the guarded or non-guarded transitions occurred,
+                        Err(#transitions_failed)
+                    }
+                }),*
+                #[allow(unreachable_patterns)]
+                _ => Err(#invalid_event),
+            }),*
+        }
+    };
+
+    // The transition is marked from its first exit function or action until it finishes, as the
+    // future may be dropped at any `.await`
+    let (process_event_code, transition_code) = if is_cancellable {
+        (
+            quote! {
+                if self.in_transition {
+                    return Err(#error_type_name::InTransition);
+                }
+                let result = self.process_event_in_transition(#temporary_context_call event).await.map(|_| ());
+                self.in_transition = false;
+                result?;
+                Ok(&self.state)
+            },
+            quote! {
+                /// Processes an event while the transition is marked by `process_event()`.
+                async fn process_event_in_transition <#event_unique_lifetimes> (
+                    &mut self,
+                    #temporary_context
+                    event: #events_type_name <#event_lifetimes #event_generic_args>
+                ) -> Result<&#states_type_name <#state_lifetimes #state_generic_args>, #error_type>
+                #process_event_bounds
+                {
+                    #process_event_code
+                }
+            },
+        )
+    } else {
+        (process_event_code, quote! {})
+    };
+
+    // Async state machines can still process the events which await nothing in the current state
+    let (try_process_event_sync_code, requires_async_error) = if is_async_state_machine {
+        let async_pairs = transitions.iter().zip(&requires_async).flat_map(
+            |((state, event_mappings), requires_async)| {
+                let state_ident = &sm.states[state];
+                let state_pattern = match sm.state_data.data_types.get(state) {
+                    Some(_) => quote! { #state_ident(..) },
+                    None => quote! { #state_ident },
+                };
+                event_mappings
+                    .iter()
+                    .zip(requires_async)
+                    .filter(|(_, requires_async)| **requires_async)
+                    .map(|((event, event_mapping), _)| {
+                        let event_ident = &event_mapping.event;
+                        let event_pattern = match sm.event_data.data_types.get(event) {
+                            Some(_) => quote! { #event_ident(..) },
+                            None => quote! { #event_ident },
+                        };
+                        quote! { (#states_type_name::#state_pattern, #events_type_name::#event_pattern) }
+                    })
+                    .collect::<Vec<_>>()
+            },
+        );
+
+        (
+            quote! {
+                /// Process an event without awaiting anything.
+                ///
+                /// Returns `Err(#error_type_name::RequiresAsync)` without processing the event if
+                /// processing it in the current state may call an async function, in which case
+                /// `process_event()` has to be used instead. Otherwise it behaves like
+                /// `process_event()`.
+                pub fn try_process_event_sync <#event_unique_lifetimes> (
+                    &mut self,
+                    #temporary_context
+                    event: #events_type_name <#event_lifetimes #event_generic_args>
+                ) -> Result<&#states_type_name <#state_lifetimes #state_generic_args>, #error_type>
+                #process_event_bounds
+                {
+                    #[allow(unreachable_patterns)]
+                    let requires_async = match (&self.state, &event) {
+                        #(#async_pairs => true,)*
+                        _ => false,
+                    };
+                    if requires_async {
+                        return Err(#error_type_name::RequiresAsync);
+                    }
+
+                    // Nothing is awaited, so the future is ready when it is polled the first time
+                    match ::smlang::poll_once(self.process_event(#temporary_context_call event)) {
+                        Some(result) => result,
+                        None => unreachable!("processing the event awaited an async function"),
+                    }
+                }
+            },
+            quote! {
+                /// When `try_process_event_sync()` is called with an event whose processing may
+                /// call an async function in the current state.
+                RequiresAsync,
+            },
+        )
+    } else {
+        (quote! {}, quote! {})
+    };
+
+    // Inside a module the items are public, as their visibility is limited by the module
+    let visibility = match &sm.module {
+        Some(_) => quote! { pub },
+        None => {
+            let visibility = &sm.visibility;
+            quote! { #visibility }
+        }
+    };
+
+    let states_attr_list = &sm.states_attr;
+    let events_attr_list = &sm.events_attr;
+    let machine_attr_list = &sm.machine_attr;
+    let error_attr_list = match &sm.error_attr {
+        Some(error_attr) => quote! { #(#error_attr)* },
+        // The events are not required to implement `Debug`, which is implemented below instead
+        None if sm.detailed_errors => quote! { #[derive(PartialEq)] },
+        None => quote! { #[derive(Debug,PartialEq)] },
+    };
+
+    // The variants of the error without data, with their messages
+    let (unit_variants, unit_messages): (Vec<_>, Vec<_>) = [
+        (
+            has_history,
+            "NoHistory",
+            "there is no previous state to return to",
+        ),
+        (
+            !final_states.is_empty(),
+            "MachineFinished",
+            "the state machine is finished",
+        ),
+        (
+            is_async_state_machine,
+            "RequiresAsync",
+            "processing the event requires async calls",
+        ),
+        (
+            is_cancellable,
+            "InTransition",
+            "a previous transition was interrupted",
+        ),
+    ]
+    .iter()
+    .filter(|(used, _, _)| *used)
+    .map(|(_, variant, message)| (Ident::new(variant, Span::call_site()), *message))
+    .unzip();
+
+    let error_code = if sm.detailed_errors {
+        let state_kind = generate_kind(
+            &state_kind_type_name,
+            &states_type_name,
+            &quote! { #state_lifetimes #state_generic_params },
+            &quote! { #state_lifetimes #state_generic_args },
+            sm.states.values(),
+            &sm.state_data,
+            &visibility,
+        );
+        let event_kind = generate_kind(
+            &event_kind_type_name,
+            &events_type_name,
+            &quote! { #event_lifetimes #event_generic_params },
+            &quote! { #event_lifetimes #event_generic_args },
+            sm.events.values(),
+            &sm.event_data,
+            &visibility,
+        );
+
+        let debug_impl = if sm.error_attr.is_none() {
+            quote! {
+                impl<#event_lifetimes #event_generic_params #context_type: core::fmt::Debug> core::fmt::Debug
+                    for #error_type_name<#event_lifetimes #event_generic_args #context_type>
+                {
+                    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                        match self {
+                            Self::InvalidEvent { state, event } => f
+                                .debug_struct("InvalidEvent")
+                                .field("state", state)
+                                .field("event", &event.kind())
+                                .finish(),
+                            Self::TransitionsFailed { state, event } => f
+                                .debug_struct("TransitionsFailed")
+                                .field("state", state)
+                                .field("event", event)
+                                .finish(),
+                            Self::GuardFailed { state, event, guard, error } => f
+                                .debug_struct("GuardFailed")
+                                .field("state", state)
+                                .field("event", event)
+                                .field("guard", guard)
+                                .field("error", error)
+                                .finish(),
+                            Self::ActionFailed { state, event, error } => f
+                                .debug_struct("ActionFailed")
+                                .field("state", state)
+                                .field("event", event)
+                                .field("error", error)
+                                .finish(),
+                            #(Self::#unit_variants => f.write_str(stringify!(#unit_variants)),)*
+                        }
+                    }
+                }
+            }
+        } else {
+            quote! {}
+        };
+
+        quote! {
+            #state_kind
+            #event_kind
+
+            /// List of possible errors, with the kinds of the state and event they occurred in.
+            #error_attr_list
+            #visibility enum #error_type_name <#event_lifetimes #event_generic_params #context_type=()> {
+                /// When an event is processed which should not come in the current state. The event
+                /// is handed back.
+                InvalidEvent {
+                    /// The state the event was processed in.
+                    state: #state_kind_type_name,
+                    /// The event.
+                    event: #events_type_name <#event_lifetimes #event_generic_args>,
+                },
+                /// When an event is processed and none of the transitions happened.
+                TransitionsFailed {
+                    /// The state the event was processed in.
+                    state: #state_kind_type_name,
+                    /// The kind of the event.
+                    event: #event_kind_type_name,
+                },
+                /// When guard is failed.
+                GuardFailed {
+                    /// The state the event was processed in.
+                    state: #state_kind_type_name,
+                    /// The kind of the event.
+                    event: #event_kind_type_name,
+                    /// The name of the guard.
+                    guard: &'static str,
+                    /// The error returned by the guard.
+                    error: #context_type,
+                },
+                /// When action returns Err
+                ActionFailed {
+                    /// The state the event was processed in.
+                    state: #state_kind_type_name,
+                    /// The kind of the event.
+                    event: #event_kind_type_name,
+                    /// The error returned by the action.
+                    error: #context_type,
+                },
+                #history_error
+                #finished_error
+                #requires_async_error
+                #in_transition_error
+            }
+
+            #debug_impl
+        }
+    } else {
+        quote! {
+            /// List of possible errors
+            #error_attr_list
+            #visibility enum #error_type_name  <T=()> {
+                /// When an event is processed which should not come in the current state.
+                InvalidEvent,
+                /// When an event is processed and none of the transitions happened.
+                TransitionsFailed,
+                /// When guard is failed.
+                GuardFailed {
+                    /// The name of the guard.
+                    guard: &'static str,
+                    /// The error returned by the guard.
+                    error: T,
+                },
+                /// When action returns Err
+                ActionFailed(T),
+                #history_error
+                #finished_error
+                #requires_async_error
+                #in_transition_error
+            }
+        }
+    };
+
+    let (error_params, error_args, display_arms) = if sm.detailed_errors {
+        (
+            quote! { #event_lifetimes #event_generic_params },
+            quote! { #event_lifetimes #event_generic_args },
+            quote! {
+                Self::InvalidEvent { state, event } => {
+                    write!(f, "invalid event `{:?}` in state `{:?}`", event.kind(), state)
+                }
+                Self::TransitionsFailed { state, event } => write!(
+                    f,
+                    "no transition of event `{:?}` happened in state `{:?}`",
+                    event, state
+                ),
+                Self::GuardFailed {
+                    state,
+                    event,
+                    guard,
+                    ..
+                } => write!(
+                    f,
+                    "guard `{}` of event `{:?}` failed in state `{:?}`",
+                    guard, event, state
+                ),
+                Self::ActionFailed { state, event, .. } => write!(
+                    f,
+                    "action of event `{:?}` failed in state `{:?}`",
+                    event, state
+                ),
+            },
+        )
+    } else {
+        (
+            quote! {},
+            quote! {},
+            quote! {
+                Self::InvalidEvent => f.write_str("invalid event in the current state"),
+                Self::TransitionsFailed => f.write_str("no transition of the event happened"),
+                Self::GuardFailed { guard, .. } => write!(f, "guard `{}` failed", guard),
+                Self::ActionFailed(_) => f.write_str("action failed"),
+            },
+        )
+    };
+
+    // Without `Debug`, which `error_attr` may replace, the error cannot implement
+    // `core::error::Error`
+    let error_is_debug = sm.error_attr.as_ref().is_none_or(|error_attr| {
+        error_attr
+            .iter()
+            .any(|attr| uses_ident(quote! { #attr }, &Ident::new("Debug", Span::call_site())))
+    });
+
+    // With a custom error, the error only implements `core::error::Error` if the custom error
+    // implements it, which is then its source
+    let error_impl = if sm.custom_error {
+        let source_patterns = if sm.detailed_errors {
+            quote! { Self::GuardFailed { error, .. } | Self::ActionFailed { error, .. } }
+        } else {
+            quote! { Self::GuardFailed { error, .. } | Self::ActionFailed(error) }
+        };
+        quote! {
+            impl<#error_params #context_type: core::error::Error + 'static> core::error::Error
+                for #error_type_name<#error_args #context_type>
+            where
+                Self: core::fmt::Debug,
+            {
+                fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+                    match self {
+                        #source_patterns => Some(error),
+                        #[allow(unreachable_patterns)]
+                        _ => None,
+                    }
+                }
+            }
+        }
+    } else if error_is_debug {
+        quote! {
+            impl<#error_params> core::error::Error for #error_type_name<#error_args>
+            where
+                Self: core::fmt::Debug,
+            {
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let error_code = quote! {
+        #error_code
+
+        impl<#error_params #context_type> core::fmt::Display for #error_type_name<#error_args #context_type> {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                match self {
+                    #display_arms
+                    #(Self::#unit_variants => f.write_str(#unit_messages),)*
+                }
+            }
+        }
+
+        #error_impl
+    };
+    // Build the states and events output
+    let code = quote! {
+        /// This trait outlines the guards and actions that need to be implemented for the state
+        /// machine.
+        #visibility trait #state_machine_context_type_name <#generic_params> {
+            #custom_error
+            #guard_list
+            #action_list
+            #entries_exits
+
+
+            /// Called at the beginning of a state machine's `process_event()`. No-op by
+            /// default but can be overridden in implementations of a state machine's
+            /// `StateMachineContext` trait.
+            #[allow(unused_variables)]
+            fn log_process_event(&self, current_state: & #states_type_name<#state_generic_args>, event: & #events_type_name<#event_generic_args>) {}
+
+            /// Called after executing a guard during `process_event()`. No-op by
+            /// default but can be overridden in implementations of a state machine's
+            /// `StateMachineContext` trait.
+            #[allow(unused_variables)]
+            fn log_guard(&self, guard: &'static str, result: bool) {}
+
+            /// Called after calling each guard of a guard expression during `process_event()`,
+            /// with the result of the guard. No-op by default but can be overridden in
+            /// implementations of a state machine's `StateMachineContext` trait.
+            #[allow(unused_variables)]
+            fn log_guard_result(&self, guard: &'static str, result: &Result<bool, #guard_error>) {}
+
+            /// Called after executing an action during `process_event()`. No-op by
+            /// default but can be overridden in implementations of a state machine's
+            /// `StateMachineContext` trait.
+            #[allow(unused_variables)]
+            fn log_action(&self, action: &'static str) {}
+
+            /// Called when transitioning to a new state as a result of an event passed to
+            /// `process_event()`. No-op by default which can be overridden in implementations
+            /// of a state machine's `StateMachineContext` trait.
+            #[allow(unused_variables)]
+            fn transition_callback(&self, old_state: & #states_type_name<#state_generic_args>, new_state: & #states_type_name<#state_generic_args>) {}
+        }
+
+        /// List of auto-generated states.
+        #[allow(missing_docs)]
+        #(#states_attr_list)*
+        #visibility enum #states_type_name <#state_lifetimes #state_generic_params> { #(#state_list),* }
+
+        /// Manually define PartialEq for #states_type_name based on variant only to address issue-#21
+        impl<#state_lifetimes #state_generic_params> PartialEq for #states_type_name <#state_lifetimes #state_generic_args> {
+            fn eq(&self, other: &Self) -> bool {
+                use core::mem::discriminant;
+                discriminant(self) == discriminant(other)
+            }
+        }
+
+        /// List of auto-generated events.
+        #[allow(missing_docs)]
+        #(#events_attr_list)*
+        #visibility enum #events_type_name <#event_lifetimes #event_generic_params> { #(#event_list),* }
+
+        /// Manually define PartialEq for #events_type_name based on variant only to address issue-#21
+        impl<#event_lifetimes #event_generic_params> PartialEq for #events_type_name <#event_lifetimes #event_generic_args> {
+            fn eq(&self, other: &Self) -> bool {
+                use core::mem::discriminant;
+                discriminant(self) == discriminant(other)
+            }
+        }
+
+        #error_code
+
+        /// State machine structure definition.
+        #(#machine_attr_list)*
+        #visibility struct #state_machine_type_name<#state_lifetimes #context_type: #state_machine_context_type_name<#generic_args>, #generic_params> {
+            state: #states_type_name <#state_lifetimes #state_generic_args>,
+            #history_field
+            #in_transition_field
+            #generics_field
+            context: #context_type
+        }
+
+        impl<#state_lifetimes #context_type: #state_machine_context_type_name<#generic_args>, #generic_params> #state_machine_type_name<#state_lifetimes #context_type, #generic_args>
+        {
+            /// Creates a new state machine with the specified starting state.
+            #[inline(always)]
+            #new_sm_code
+
+            /// Creates a new state machine with an initial state.
+            #[inline(always)]
+            pub const fn new_with_state(context: #context_type, initial_state: #states_type_name <#state_lifetimes #state_generic_args>) -> Self {
+                #state_machine_type_name {
+                    state: initial_state,
+                    #history_init
+                    #in_transition_init
+                    #generics_init
+                    context
+                }
+            }
+
+            #history_code
+
+            #in_transition_code
+
+            /// Returns the current state.
+            #[inline(always)]
+            pub fn state(&self) -> &#states_type_name <#state_lifetimes #state_generic_args> {
+                &self.state
+            }
+
+            /// Returns `true` if the machine is in a final state, after which no more events are
+            /// processed.
+            #[inline(always)]
+            pub fn is_finished(&self) -> bool {
+                #is_finished_code
+            }
+
+            /// Returns the current context.
+            #[inline(always)]
+            pub fn context(&self) -> &#context_type {
+                &self.context
+            }
+
+            /// Returns the current context as a mutable reference.
+            #[inline(always)]
+            pub fn context_mut(&mut self) -> &mut #context_type {
+                &mut self.context
+            }
+
+            /// Process an event.
+            ///
+            /// It will return `Ok(&NextState)` if the transition was successful, or `Err(#error_type_name)`
+            /// if there was an error in the transition.
+            pub #is_async fn process_event <#event_unique_lifetimes> (
+                &mut self,
+                #temporary_context
+                event: #events_type_name <#event_lifetimes #event_generic_args>
+            ) -> Result<&#states_type_name <#state_lifetimes #state_generic_args>, #error_type>
+            #process_event_bounds
+            {
+                #process_event_code
+            }
+
+            #transition_code
+
+            #try_process_event_sync_code
+
+            #run_code
+        }
+    };
+
+    match &sm.module {
+        Some(module) => {
+            let visibility = &sm.visibility;
+            quote! {
+                /// The generated state machine.
+                #visibility mod #module {
+                    use super::*;
+
+                    #code
+                }
+            }
+        }
+        None => code,
+    }
+}
+
+// The `async` keyword and the return type of a function of the context trait. With `async_send:
+// true` an async function returns a `Send` future instead, as an `async fn` in a trait can't
+// require it.
+fn async_signature(
+    is_async: bool,
+    send: bool,
+    output: Option<TokenStream>,
+) -> (TokenStream, TokenStream) {
+    match (is_async, send, output) {
+        (true, true, output) => {
+            let output = output.unwrap_or_else(|| quote! { () });
+            (
+                quote! {},
+                quote! { -> impl core::future::Future<Output = #output> + Send },
+            )
+        }
+        (true, false, Some(output)) => (quote! { async }, quote! { -> #output }),
+        (true, false, None) => (quote! { async }, quote! {}),
+        (false, _, Some(output)) => (quote! {}, quote! { -> #output }),
+        (false, _, None) => (quote! {}, quote! {}),
+    }
+}
+
+fn generate_action(
+    action: &Option<AsyncIdent>,
+    temporary_context_call: &TokenStream,
+    g_a_param: &TokenStream,
+    action_failed: &TokenStream,
+    fallible: bool,
+    on_failure: Option<TokenStream>,
+) -> (bool, TokenStream) {
+    let mut is_async = false;
+    let code = if let Some(async_ident) = action {
+        let action_ident = &async_ident.ident;
+        let action_await = if async_ident.is_async {
+            is_async = true;
+            quote! { .await }
+        } else {
+            quote! {}
+        };
+        let call =
+            quote! { self.context.#action_ident(#temporary_context_call #g_a_param) #action_await };
+        let call = match on_failure {
+            _ if !async_ident.is_fallible(fallible) => call,
+            Some(on_failure) => quote! {
+                match #call {
+                    Ok(data) => data,
+                    #on_failure
+                }
+            },
+            None => quote! { #call.map_err(#action_failed)? },
+        };
+        quote! {
+            // ACTION
+            #[allow(clippy::let_unit_value, clippy::redundant_closure_call)]
+            let _data = #call;
+            self.context.log_action(stringify!(#action_ident));
+        }
+    } else {
+        quote! {}
+    };
+    (is_async, code)
+}
+
+// The entry hook and the declared entry action of a state.
+fn generate_state_entry(
+    sm: &ParsedStateMachine,
+    state: &str,
+    entry_exit_await: &TokenStream,
+) -> (bool, TokenStream) {
+    let entry_ident = format_ident!("on_entry_{}", string_morph::to_snake_case(state));
+    let (is_async, entry_action_code) = generate_state_action(
+        sm.state_declarations
+            .get(state)
+            .and_then(|declaration| declaration.entry.as_ref()),
+    );
+    let code = quote! {
+        self.context.#entry_ident()#entry_exit_await;
+        #entry_action_code
+    };
+    (is_async || sm.entry_exit_async, code)
+}
+
+fn generate_state_action(action: Option<&AsyncIdent>) -> (bool, TokenStream) {
+    match action {
+        Some(AsyncIdent {
+            ident: action_ident,
+            is_async,
+            ..
+        }) => {
+            let action_await = if *is_async {
+                quote! { .await }
+            } else {
+                quote! {}
+            };
+            let code = quote! {
+                self.context.#action_ident() #action_await;
+                self.context.log_action(stringify!(#action_ident));
+            };
+            (*is_async, code)
+        }
+        None => (false, quote! {}),
+    }
+}
+
+// The enum of the kinds of states or events, without their data, and the `kind()` method returning
+// them.
+fn generate_kind<'a>(
+    kind_type_name: &Ident,
+    type_name: &Ident,
+    params: &TokenStream,
+    args: &TokenStream,
+    variants: impl Iterator<Item = &'a Ident>,
+    data: &DataDefinitions,
+    visibility: &TokenStream,
+) -> TokenStream {
+    let mut variants: Vec<_> = variants.collect();
+    variants.sort_by_key(|variant| variant.to_string());
+    let patterns = variants.iter().map(|variant| {
+        if data.data_types.contains_key(&variant.to_string()) {
+            quote! { #variant(..) }
+        } else {
+            quote! { #variant }
+        }
+    });
+
+    quote! {
+        /// The kinds of the variants of
+        #[doc = concat!("[", stringify!(#type_name), "]")]
+        /// without their data.
+        #[allow(missing_docs)]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        #visibility enum #kind_type_name { #(#variants),* }
+
+        impl<#params> #type_name <#args> {
+            /// Returns the kind of the variant, without its data.
+            pub fn kind(&self) -> #kind_type_name {
+                match self {
+                    #(Self::#patterns => #kind_type_name::#variants),*
+                }
+            }
+        }
+    }
+}
+
+// The declarations and the arguments of the generic parameters selected by `filter`, each followed
+// by a comma.
+fn generic_parameters<F>(sm: &ParsedStateMachine, filter: F) -> (TokenStream, TokenStream)
+where
+    F: Fn(&Ident) -> bool,
+{
+    let params: Vec<_> = sm
+        .generics
+        .params
+        .iter()
+        .filter(|param| generic_param_ident(param).is_some_and(&filter))
+        .collect();
+    let args = params.iter().filter_map(|param| generic_param_ident(param));
+    (quote! { #(#params,)* }, quote! { #(#args,)* })
+}
+
+fn generic_param_ident(param: &GenericParam) -> Option<&Ident> {
+    match param {
+        GenericParam::Type(param) => Some(&param.ident),
+        GenericParam::Const(param) => Some(&param.ident),
+        GenericParam::Lifetime(_) => None,
+    }
+}
+
+// Checks if an identifier is used in the tokens, e.g. a generic parameter in a data type.
+fn uses_ident(tokens: TokenStream, ident: &Ident) -> bool {
+    tokens.into_iter().any(|token| match token {
+        TokenTree::Ident(token) => &token == ident,
+        TokenTree::Group(group) => uses_ident(group.stream(), ident),
+        _ => false,
+    })
+}
+
+// The errors returned by a transition.
+struct TransitionErrors {
+    action_failed: TokenStream,
+    transitions_failed: TokenStream,
+    // The fields with the kinds of the state and event, with detailed errors
+    kinds: TokenStream,
+    // The states entered when the guard or action of each transition fails
+    error_states: Vec<Option<Ident>>,
+}
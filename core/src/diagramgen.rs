@@ -17,7 +17,7 @@ pub fn generate_diagram(sm: &ParsedStateMachine) -> String {
                     transition
                         .guard
                         .as_ref()
-                        .map(|i| i.to_string())
+                        .map(crate::export::guard_string)
                         .unwrap_or_else(|| "_".to_string()),
                     transition
                         .action
@@ -43,11 +43,34 @@ pub fn generate_diagram(sm: &ParsedStateMachine) -> String {
 
     let state_string = diagram_states
         .map(|s| {
+            // Entry and exit actions are listed below the state name, final states get a
+            // double border
+            let mut label = s.to_string();
+            let mut peripheries = 1;
+            if let Some(declaration) = sm.state_declarations.get(s) {
+                if declaration.is_final {
+                    peripheries = 2;
+                }
+                if let Some(entry) = &declaration.entry {
+                    label.push_str(&format!("\\nentry / {}", entry.ident));
+                }
+                if let Some(exit) = &declaration.exit {
+                    label.push_str(&format!("\\nexit / {}", exit.ident));
+                }
+                if let Some(activity) = &declaration.activity {
+                    label.push_str(&format!("\\ndo / {}", activity.ident));
+                }
+            }
             format!(
-                "\t{} [shape=box color=\"red\" fillcolor=\"#ffbb33\" style=filled]",
-                s
+                "\t{} [shape=box color=\"red\" fillcolor=\"#ffbb33\" style=filled label=\"{}\" peripheries={}]",
+                s, label, peripheries
             )
         })
+        .chain(
+            // The history state is drawn as a pseudo-state
+            sm.has_history()
+                .then(|| format!("\t{} [shape=circle]", output_state::HISTORY_STATE)),
+        )
         .collect::<Vec<String>>();
     let event_string = diagram_events
         .iter()
@@ -0,0 +1,340 @@
+use crate::parser::event::Transition;
+use crate::parser::output_state::HISTORY_STATE;
+use crate::parser::state_machine::ActionFailure;
+use crate::parser::transition::GuardExpression;
+use crate::parser::{AsyncIdent, ParsedStateMachine};
+use quote::ToTokens;
+use syn::Type;
+
+/// Generates an SCXML document of the state machine, which can be imported again with
+/// `statemachine_scxml!`.
+///
+/// Conditions and scripts are written in the DSL rather than a standard data model, which the
+/// document declares with `datamodel="smlang"`.
+pub fn generate_scxml(sm: &ParsedStateMachine) -> String {
+    let mut scxml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    scxml.push_str(
+        "<scxml xmlns=\"http://www.w3.org/2005/07/scxml\" version=\"1.0\" datamodel=\"smlang\"",
+    );
+    if let Some(name) = &sm.name {
+        scxml.push_str(&format!(" name=\"{}\"", name));
+    }
+    scxml.push_str(&format!(" initial=\"{}\">\n", sm.starting_state));
+
+    for state in sorted_states(sm) {
+        let declaration = sm.state_declarations.get(&state);
+        let is_final = declaration.is_some_and(|declaration| declaration.is_final);
+        let element = if is_final { "final" } else { "state" };
+        let mut body = String::new();
+        if let Some(declaration) = declaration {
+            if let Some(entry) = &declaration.entry {
+                body.push_str(&format!(
+                    "        <onentry><script>{}</script></onentry>\n",
+                    action(entry)
+                ));
+            }
+            if let Some(exit) = &declaration.exit {
+                body.push_str(&format!(
+                    "        <onexit><script>{}</script></onexit>\n",
+                    action(exit)
+                ));
+            }
+            if let Some(activity) = &declaration.activity {
+                body.push_str(&format!(
+                    "        <!-- do / {} is not exported -->\n",
+                    action(activity)
+                ));
+            }
+        }
+
+        for (event, transition) in sorted_transitions(sm, &state) {
+            // SCXML only has history states within compound states
+            if transition.out_state == HISTORY_STATE {
+                body.push_str(&format!(
+                    "        <!-- {} + {} = {} is not exported -->\n",
+                    state, event, HISTORY_STATE
+                ));
+                continue;
+            }
+
+            body.push_str(&format!("        <transition event=\"{}\"", event));
+            if let Some(guard) = &transition.guard {
+                body.push_str(&format!(" cond=\"{}\"", escape_xml(&guard_string(guard))));
+            }
+            // Internal transitions do not leave the state, which is a transition without target
+            if transition.out_state != state {
+                body.push_str(&format!(" target=\"{}\"", transition.out_state));
+            }
+            match &transition.action {
+                Some(transition_action) => body.push_str(&format!(
+                    ">\n            <script>{}</script>\n        </transition>\n",
+                    action(transition_action)
+                )),
+                None => body.push_str("/>\n"),
+            }
+        }
+
+        if body.is_empty() {
+            scxml.push_str(&format!("    <{} id=\"{}\"/>\n", element, state));
+        } else {
+            scxml.push_str(&format!(
+                "    <{0} id=\"{1}\">\n{2}    </{0}>\n",
+                element, state, body
+            ));
+        }
+    }
+
+    scxml.push_str("</scxml>\n");
+    scxml
+}
+
+/// Generates a JSON description of the state machine, see `docs/export.md` for the schema.
+pub fn generate_json(sm: &ParsedStateMachine) -> String {
+    let states = sorted_states(sm)
+        .into_iter()
+        .map(|state| {
+            let declaration = sm.state_declarations.get(&state);
+            let state_action = |declared: Option<&AsyncIdent>| {
+                declared.map_or("null".to_string(), |declared| json_string(&action(declared)))
+            };
+            format!(
+                "    {{ \"name\": {}, \"data\": {}, \"final\": {}, \"entry\": {}, \"exit\": {}, \"activity\": {} }}",
+                json_string(&state),
+                data_type(sm.state_data.data_types.get(&state)),
+                declaration.is_some_and(|declaration| declaration.is_final),
+                state_action(declaration.and_then(|declaration| declaration.entry.as_ref())),
+                state_action(declaration.and_then(|declaration| declaration.exit.as_ref())),
+                state_action(declaration.and_then(|declaration| declaration.activity.as_ref())),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let mut events: Vec<_> = sm.events.keys().collect();
+    events.sort();
+    let events = events
+        .into_iter()
+        .map(|event| {
+            format!(
+                "    {{ \"name\": {}, \"data\": {} }}",
+                json_string(event),
+                data_type(sm.event_data.data_types.get(event))
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let transitions = sorted_states(sm)
+        .into_iter()
+        .flat_map(|state| {
+            sorted_transitions(sm, &state)
+                .into_iter()
+                .map(move |(event, transition)| {
+                    format!(
+                        "    {{ \"from\": {}, \"event\": {}, \"guard\": {}, \"action\": {}, \"to\": {}, \"error_state\": {} }}",
+                        json_string(&state),
+                        json_string(&event),
+                        transition
+                            .guard
+                            .as_ref()
+                            .map_or("null".to_string(), |guard| json_string(&guard_string(guard))),
+                        transition
+                            .action
+                            .as_ref()
+                            .map_or("null".to_string(), |transition_action| {
+                                json_string(&action(transition_action))
+                            }),
+                        json_string(&transition.out_state.to_string()),
+                        optional_name(transition.error_state.as_ref()),
+                    )
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let action_failure = match &sm.action_failure {
+        ActionFailure::ExitFirst => "exit_first".to_string(),
+        ActionFailure::ActionFirst => "action_first".to_string(),
+        ActionFailure::Reenter => "reenter".to_string(),
+        ActionFailure::Enter(state) => format!("enter({})", state),
+    };
+
+    format!(
+        "{{\n  \"name\": {},\n  \"starting_state\": {},\n  \"on_error\": {},\n  \"action_failure\": {},\n  \"states\": [\n{}\n  ],\n  \"events\": [\n{}\n  ],\n  \"transitions\": [\n{}\n  ]\n}}\n",
+        optional_name(sm.name.as_ref()),
+        json_string(&sm.starting_state.to_string()),
+        optional_name(sm.on_error.as_ref()),
+        json_string(&action_failure),
+        states.join(",\n"),
+        events.join(",\n"),
+        transitions.join(",\n"),
+    )
+}
+
+/// The names of all states, sorted.
+pub fn sorted_states(sm: &ParsedStateMachine) -> Vec<String> {
+    let mut states: Vec<_> = sm.states.keys().cloned().collect();
+    states.sort();
+    states
+}
+
+/// The events and transitions of a state, including those expanded from wildcards, in the order
+/// they are defined.
+pub fn sorted_transitions<'a>(
+    sm: &'a ParsedStateMachine,
+    state: &str,
+) -> Vec<(String, &'a Transition)> {
+    let mut transitions: Vec<_> = sm.states_events_mapping[state]
+        .values()
+        .flat_map(|event_mapping| {
+            event_mapping
+                .transitions
+                .iter()
+                .map(move |transition| (event_mapping.event.to_string(), transition))
+        })
+        .collect();
+    transitions.sort_by_key(|(event, transition)| (transition.index, event.clone()));
+    transitions
+}
+
+/// Formats an action as it is written in the DSL.
+pub fn action(action: &AsyncIdent) -> String {
+    let suffix = match action.fallible {
+        Some(false) => "!",
+        Some(true) => "?",
+        None => "",
+    };
+    if action.is_async {
+        format!("async {}{}", action.ident, suffix)
+    } else {
+        format!("{}{}", action.ident, suffix)
+    }
+}
+
+/// Formats a guard expression as it is written in the DSL.
+pub fn guard_string(guard: &GuardExpression) -> String {
+    match guard {
+        GuardExpression::Guard(guard) => action(guard),
+        GuardExpression::Not(expr) => format!("!{}", guard_string(expr)),
+        GuardExpression::Group(expr) => format!("({})", guard_string(expr)),
+        GuardExpression::And(lhs, rhs) => {
+            format!("{} && {}", guard_string(lhs), guard_string(rhs))
+        }
+        GuardExpression::Or(lhs, rhs) => {
+            format!("{} || {}", guard_string(lhs), guard_string(rhs))
+        }
+    }
+}
+
+fn data_type(data_type: Option<&Type>) -> String {
+    data_type.map_or("null".to_string(), |data_type| {
+        json_string(&data_type.to_token_stream().to_string())
+    })
+}
+
+// A JSON string of the name, or `null`.
+fn optional_name(name: Option<&syn::Ident>) -> String {
+    name.map_or("null".to_string(), |name| json_string(&name.to_string()))
+}
+
+fn json_string(value: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    fn door() -> ParsedStateMachine {
+        ParsedStateMachine::new(parse_quote! {
+            name: Door,
+            on_error: Broken,
+            action_failure: reenter,
+            states: {
+                Opened { entry / light_on, exit / async light_off },
+                Broken { final },
+            },
+            transitions: {
+                *Closed + Open(u32) [unlocked && !async jammed] / open = Opened ! Closed,
+                Opened + Close = Closed,
+                Opened + Ping = _,
+                _ + Break = Broken,
+            }
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn scxml() {
+        assert_eq!(
+            generate_scxml(&door()),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<scxml xmlns="http://www.w3.org/2005/07/scxml" version="1.0" datamodel="smlang" name="Door" initial="Closed">
+    <final id="Broken"/>
+    <state id="Closed">
+        <transition event="Open" cond="unlocked &amp;&amp; !async jammed" target="Opened">
+            <script>open</script>
+        </transition>
+        <transition event="Break" target="Broken"/>
+    </state>
+    <state id="Opened">
+        <onentry><script>light_on</script></onentry>
+        <onexit><script>async light_off</script></onexit>
+        <transition event="Close" target="Closed"/>
+        <transition event="Ping"/>
+        <transition event="Break" target="Broken"/>
+    </state>
+</scxml>
+"#
+        );
+    }
+
+    #[test]
+    fn json() {
+        assert_eq!(
+            generate_json(&door()),
+            r#"{
+  "name": "Door",
+  "starting_state": "Closed",
+  "on_error": "Broken",
+  "action_failure": "reenter",
+  "states": [
+    { "name": "Broken", "data": null, "final": true, "entry": null, "exit": null, "activity": null },
+    { "name": "Closed", "data": null, "final": false, "entry": null, "exit": null, "activity": null },
+    { "name": "Opened", "data": null, "final": false, "entry": "light_on", "exit": "async light_off", "activity": null }
+  ],
+  "events": [
+    { "name": "Break", "data": null },
+    { "name": "Close", "data": null },
+    { "name": "Open", "data": "u32" },
+    { "name": "Ping", "data": null }
+  ],
+  "transitions": [
+    { "from": "Closed", "event": "Open", "guard": "unlocked && !async jammed", "action": "open", "to": "Opened", "error_state": "Closed" },
+    { "from": "Closed", "event": "Break", "guard": null, "action": null, "to": "Broken", "error_state": null },
+    { "from": "Opened", "event": "Close", "guard": null, "action": null, "to": "Closed", "error_state": null },
+    { "from": "Opened", "event": "Ping", "guard": null, "action": null, "to": "Opened", "error_state": null },
+    { "from": "Opened", "event": "Break", "guard": null, "action": null, "to": "Broken", "error_state": null }
+  ]
+}
+"#
+        );
+    }
+}
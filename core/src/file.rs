@@ -0,0 +1,470 @@
+use crate::parser::state::StateDeclaration;
+use crate::parser::state_machine::StateMachine;
+use crate::parser::transition::StateTransitions;
+use crate::parser::{Errors, Origin, ParsedStateMachine};
+use crate::validation::Warning;
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use syn::parse::{ParseStream, Parser};
+use syn::{parse, LitStr};
+
+/// Expands a state machine whose DSL is read from a file relative to `CARGO_MANIFEST_DIR`.
+pub fn expand(path: &LitStr) -> parse::Result<TokenStream> {
+    let (full_path, source) = read(path)?;
+    let file = path.value();
+
+    expand_source(&source, path, &full_path, |location| {
+        format!("{}:{}:{}", file, location.line, location.column)
+    })
+}
+
+/// Reads a file relative to `CARGO_MANIFEST_DIR`, returning its full path and contents.
+pub fn read(path: &LitStr) -> parse::Result<(PathBuf, String)> {
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").map_err(|_| {
+        parse::Error::new(
+            path.span(),
+            "`CARGO_MANIFEST_DIR` is not set, so the state machine file cannot be found.",
+        )
+    })?;
+    let full_path = PathBuf::from(manifest_dir).join(path.value());
+    let source = std::fs::read_to_string(&full_path).map_err(|error| {
+        parse::Error::new(
+            path.span(),
+            format!("Failed to read `{}`: {}", full_path.display(), error),
+        )
+    })?;
+
+    Ok((full_path, source))
+}
+
+/// Expands a state machine from DSL source which was read from `full_path`. Errors and warnings
+/// are reported at the `path` literal, prefixed with the location given by `location` for the
+/// position of the entry they belong to in the source.
+pub fn expand_source<F>(
+    source: &str,
+    path: &LitStr,
+    full_path: &Path,
+    location: F,
+) -> parse::Result<TokenStream>
+where
+    F: Fn(Location) -> String,
+{
+    match analyse(source, location) {
+        Ok((sm, warnings)) => {
+            let mut code = crate::expand(&sm)?;
+            code.extend(warnings.into_iter().map(|warning| {
+                Warning {
+                    span: path.span(),
+                    message: warning.message,
+                }
+                .into_token_stream()
+            }));
+
+            // Rebuild when the file changes
+            let full_path = full_path.to_string_lossy();
+            code.extend(quote! {
+                const _: &[u8] = include_bytes!(#full_path);
+            });
+
+            Ok(code)
+        }
+        Err(errors) => Err(errors
+            .into_iter()
+            .map(|error| parse::Error::new(path.span(), error))
+            .reduce(|mut errors, error| {
+                errors.combine(error);
+                errors
+            })
+            .unwrap()),
+    }
+}
+
+// Tokens created from a string by the compiler only have the span of the macro invocation, so
+// every option and every item of a block is parsed on its own, and the transitions and
+// declarations it adds carry its location as their origin, which prefixes their errors and
+// warnings.
+pub(crate) fn analyse<F>(
+    source: &str,
+    location: F,
+) -> parse::Result<(ParsedStateMachine, Vec<Warning>)>
+where
+    F: Fn(Location) -> String,
+{
+    let mut sm = StateMachine::new();
+    let mut errors = Errors::default();
+
+    for entry in entries(source) {
+        match entry {
+            Entry::Option(range) => {
+                let origin = Some(location(Location::of(source, range.start)));
+                let keyword = source[range.clone()]
+                    .split(|c: char| !c.is_alphanumeric() && c != '_')
+                    .next()
+                    .unwrap_or_default()
+                    .to_string();
+
+                parse_part(
+                    &mut sm,
+                    &mut errors,
+                    &source[range],
+                    &origin,
+                    |sm, input, errors| sm.parse_entry(input, errors),
+                );
+                if let Some(origin) = origin {
+                    sm.origins.insert(keyword, origin);
+                }
+            }
+            Entry::Block { states, items } => {
+                for range in items {
+                    let origin = Some(location(Location::of(source, range.start)));
+                    parse_part(
+                        &mut sm,
+                        &mut errors,
+                        &source[range],
+                        &origin,
+                        |sm, input, _| {
+                            if states {
+                                sm.state_declarations
+                                    .push(input.parse::<StateDeclaration>()?);
+                            } else {
+                                sm.add_transitions(input.parse::<StateTransitions>()?);
+                            }
+                            Ok(())
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    errors.finish()?;
+    crate::check(sm)
+}
+
+// Parses one part of the source into the state machine, which must consume all of it. The
+// transitions and declarations it adds, and its errors, get the origin of the part.
+fn parse_part<P>(sm: &mut StateMachine, errors: &mut Errors, part: &str, origin: &Origin, parse: P)
+where
+    P: FnOnce(&mut StateMachine, ParseStream, &mut Errors) -> parse::Result<()>,
+{
+    let (transitions, declarations) = (sm.transitions.len(), sm.state_declarations.len());
+    let mut part_errors = Errors::default();
+
+    let result = tokenize(part).and_then(|tokens| {
+        (|input: ParseStream| parse(&mut *sm, input, &mut part_errors)).parse2(tokens)
+    });
+    part_errors.check(result);
+    errors.check_at(origin, part_errors.finish());
+
+    for transition in &mut sm.transitions[transitions..] {
+        transition.origin.clone_from(origin);
+    }
+    for declaration in &mut sm.state_declarations[declarations..] {
+        declaration.origin.clone_from(origin);
+    }
+}
+
+fn tokenize(source: &str) -> parse::Result<TokenStream> {
+    source
+        .parse()
+        .map_err(|error: proc_macro2::LexError| parse::Error::new(error.span(), error))
+}
+
+/// A position in the DSL source, with 1-based line and column numbers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Location {
+    fn of(source: &str, offset: usize) -> Self {
+        let before = &source[..offset];
+        let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+
+        Location {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Comma,
+    Other,
+}
+
+// Splits the source into the tokens which matter for finding the entries of the DSL, skipping
+// whitespace and comments. Literals are kept as single tokens so their contents are not mistaken
+// for delimiters.
+fn lex(source: &str) -> Vec<(Token, Range<usize>)> {
+    let bytes = source.as_bytes();
+    let skip_quoted = |mut i: usize, quote: u8| {
+        while i < bytes.len() && bytes[i] != quote {
+            i += if bytes[i] == b'\\' { 2 } else { 1 };
+        }
+        (i + 1).min(bytes.len())
+    };
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let start = i;
+        let token = match bytes[i] {
+            b if b.is_ascii_whitespace() => {
+                i += 1;
+                continue;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                i = source[i..].find('\n').map_or(bytes.len(), |end| i + end);
+                continue;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                let mut depth = 0;
+                while i < bytes.len() {
+                    if source[i..].starts_with("/*") {
+                        depth += 1;
+                        i += 2;
+                    } else if source[i..].starts_with("*/") {
+                        depth -= 1;
+                        i += 2;
+                        if depth == 0 {
+                            break;
+                        }
+                    } else {
+                        i += 1;
+                    }
+                }
+                continue;
+            }
+            b'"' => {
+                i = skip_quoted(i + 1, b'"');
+                Token::Other
+            }
+            b'\'' => {
+                // A character literal, or a lifetime which ends with the identifier
+                let escaped = bytes.get(i + 1) == Some(&b'\\');
+                let next = source[i + 1..].chars().next().map_or(0, char::len_utf8);
+                i = if escaped || bytes.get(i + 1 + next) == Some(&b'\'') {
+                    skip_quoted(i + 1, b'\'')
+                } else {
+                    i + 1
+                };
+                Token::Other
+            }
+            b'(' | b'[' | b'{' => {
+                i += 1;
+                Token::Open
+            }
+            b')' | b']' | b'}' => {
+                i += 1;
+                Token::Close
+            }
+            b',' => {
+                i += 1;
+                Token::Comma
+            }
+            b if b.is_ascii_alphanumeric() || b == b'_' => {
+                while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                    i += 1;
+                }
+                // Raw strings
+                if source[start..i] == *"r" || source[start..i] == *"br" {
+                    let hashes = source[i..].len() - source[i..].trim_start_matches('#').len();
+                    if bytes.get(i + hashes) == Some(&b'"') {
+                        let end = format!("\"{}", "#".repeat(hashes));
+                        i = source[i + hashes + 1..]
+                            .find(&end)
+                            .map_or(bytes.len(), |end| i + hashes + 1 + end + 1 + hashes);
+                    }
+                }
+                Token::Other
+            }
+            _ => {
+                i += source[i..].chars().next().map_or(1, char::len_utf8);
+                Token::Other
+            }
+        };
+        tokens.push((token, start..i));
+    }
+
+    tokens
+}
+
+// Splits tokens into the ranges of the comma separated items at the outermost depth.
+fn split(tokens: &[(Token, Range<usize>)]) -> Vec<&[(Token, Range<usize>)]> {
+    let mut items = Vec::new();
+    let mut depth = 0usize;
+    let mut item_start = 0;
+
+    for (i, (token, _)) in tokens.iter().enumerate() {
+        match token {
+            Token::Open => depth += 1,
+            Token::Close => depth = depth.saturating_sub(1),
+            Token::Comma if depth == 0 => {
+                items.push(&tokens[item_start..i]);
+                item_start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&tokens[item_start..]);
+
+    items.into_iter().filter(|item| !item.is_empty()).collect()
+}
+
+fn span(tokens: &[(Token, Range<usize>)]) -> Range<usize> {
+    tokens[0].1.start..tokens[tokens.len() - 1].1.end
+}
+
+// An option of the DSL, or the byte ranges of the items of a `states` or `transitions` block.
+enum Entry {
+    Option(Range<usize>),
+    Block {
+        states: bool,
+        items: Vec<Range<usize>>,
+    },
+}
+
+// Splits the source into its options and blocks.
+fn entries(source: &str) -> Vec<Entry> {
+    let tokens = lex(source);
+    split(&tokens)
+        .into_iter()
+        .map(|entry| {
+            let text = |i: usize| &source[entry[i].1.clone()];
+            let block = matches!(text(0), "states" | "transitions")
+                && entry.len() >= 4
+                && text(1) == ":"
+                && text(2) == "{"
+                && text(entry.len() - 1) == "}";
+
+            if block {
+                Entry::Block {
+                    states: text(0) == "states",
+                    items: split(&entry[3..entry.len() - 1])
+                        .into_iter()
+                        .map(span)
+                        .collect(),
+                }
+            } else {
+                Entry::Option(span(entry))
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The error or warning messages of analysing the source of `machine.sm`.
+    fn diagnostics(source: &str) -> (Vec<String>, Vec<String>) {
+        let location =
+            |location: Location| format!("machine.sm:{}:{}", location.line, location.column);
+
+        match analyse(source, location) {
+            Ok((_, warnings)) => (
+                Vec::new(),
+                warnings
+                    .into_iter()
+                    .map(|warning| warning.message)
+                    .collect(),
+            ),
+            Err(errors) => (
+                errors.into_iter().map(|error| error.to_string()).collect(),
+                Vec::new(),
+            ),
+        }
+    }
+
+    #[test]
+    fn locate_errors() {
+        let source =
+            "transitions: {\n    *Idle + Start = Running,\n    Running + + Stop = Idle,\n}\n";
+
+        let (errors, warnings) = diagnostics(source);
+        assert_eq!(errors, ["machine.sm:3:5: expected identifier"]);
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn locate_warnings() {
+        let source = "transitions: {\n    *Idle + Start = Running,\n    Lost + Find = Idle,\n}\n";
+
+        let (errors, warnings) = diagnostics(source);
+        assert!(errors.is_empty());
+        assert_eq!(
+            warnings,
+            [
+                "machine.sm:3:5: State `Lost` is unreachable from the starting state `Idle`",
+                "machine.sm:3:5: Event `Find` can never be processed, as no reachable state has a transition for it",
+            ]
+        );
+    }
+
+    #[test]
+    fn locate_conflicts() {
+        let source = "// Commas in comments, like this one, and lifetimes are skipped
+name: Door,
+transitions: {
+    *Closed + Open /* , */ / open = Opened(&'a [u8]),
+    Opened(&'a [u8]) + Close = Closed,
+    Closed + Open / open = Opened(&'a [u8]),
+}
+";
+
+        let (errors, warnings) = diagnostics(source);
+        assert_eq!(
+            errors,
+            [
+                "machine.sm:6:5: Closed + Open: State and event combination specified multiple times, remove duplicates.",
+                "machine.sm:4:5: Closed + Open: first defined here",
+            ]
+        );
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn locate_declarations_before_transitions() {
+        let source = "states: {
+    Foo { entry / enter_foo },
+},
+transitions: {
+    *Init + Go = Done,
+}
+";
+
+        let (errors, warnings) = diagnostics(source);
+        assert_eq!(
+            errors,
+            ["machine.sm:2:5: State `Foo` is declared but not used in any transition."]
+        );
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn locate_conflicts_after_starting_state() {
+        let source = "transitions: {
+    A + Go [g] = B,
+    B + Go [g] = C,
+    *Init(u8) + Go [g] = A,
+}
+";
+
+        let (errors, warnings) = diagnostics(source);
+        assert_eq!(
+            errors,
+            [
+                "machine.sm:4:5: Guard `g` can only be reused when all input states and events have the same data",
+                "machine.sm:2:5: Guard `g` is first used here",
+            ]
+        );
+        assert!(warnings.is_empty());
+    }
+}
@@ -0,0 +1,141 @@
+//! Parser, validation and code generation of the [smlang](https://docs.rs/smlang) state machine
+//! DSL.
+//!
+//! This is the implementation of the `statemachine!`, `statemachine_file!` and
+//! `statemachine_scxml!` macros, the latter with the `scxml` feature, usable outside of a procedural macro by build scripts and
+//! tools. [`generate`] expands the DSL as `statemachine!` does, while [`analyse`] gives the
+//! checked model of the state machine, a [`ParsedStateMachine`](parser::ParsedStateMachine).
+
+#![recursion_limit = "512"]
+
+pub mod codegen;
+pub mod diagramgen;
+pub mod export;
+pub mod file;
+pub mod parser;
+#[cfg(feature = "scxml")]
+pub mod scxml;
+pub mod validation;
+
+use proc_macro2::TokenStream;
+use quote::ToTokens;
+
+// dot -Tsvg statemachine.gv -o statemachine.svg
+
+/// Parses, validates and generates the code of a state machine, as done by `statemachine!`.
+///
+/// Warnings are emitted by the generated code.
+///
+/// ```
+/// let code = smlang_core::generate(quote::quote! {
+///     transitions: {
+///         *Idle + Start = Running,
+///     }
+/// })
+/// .unwrap();
+/// assert!(code.to_string().contains("pub enum States"));
+/// ```
+pub fn generate(input: TokenStream) -> syn::Result<TokenStream> {
+    let (sm, warnings) = analyse(input)?;
+    let mut code = expand(&sm)?;
+    code.extend(warnings.iter().map(ToTokens::into_token_stream));
+    Ok(code)
+}
+
+/// Parses and validates the state machine.
+pub fn analyse(
+    input: TokenStream,
+) -> syn::Result<(parser::ParsedStateMachine, Vec<validation::Warning>)> {
+    // Parse the syntax into structures
+    check(syn::parse2(input)?)
+}
+
+/// Validates the parsed state machine.
+fn check(
+    sm: parser::state_machine::StateMachine,
+) -> syn::Result<(parser::ParsedStateMachine, Vec<validation::Warning>)> {
+    // Validate syntax
+    let sm = parser::ParsedStateMachine::new(sm)?;
+
+    // Validate the parsed state machine before generating code.
+    let warnings = validation::validate(&sm)?;
+
+    Ok((sm, warnings))
+}
+
+/// Generates the code, and the diagram and exports if enabled, of a valid state machine.
+fn expand(sm: &parser::ParsedStateMachine) -> syn::Result<TokenStream> {
+    #[cfg(feature = "graphviz")]
+    {
+        use std::hash::{Hash, Hasher};
+        use std::io::Write;
+
+        // Generate dot syntax for the statemachine.
+        let diagram = diagramgen::generate_diagram(sm);
+        let diagram_name = if let Some(name) = &sm.name {
+            name.to_string()
+        } else {
+            let mut diagram_hasher = std::collections::hash_map::DefaultHasher::new();
+            diagram.hash(&mut diagram_hasher);
+            format!("smlang{:010x}", diagram_hasher.finish())
+        };
+
+        // Start the 'dot' process.
+        let mut process = std::process::Command::new("dot")
+            .args(["-Tsvg", "-o", &format!("statemachine_{diagram_name}.svg")])
+            .stdin(std::process::Stdio::piped())
+            .spawn()
+            .expect("Failed to execute 'dot'. Are you sure graphviz is installed?");
+
+        // Write the dot syntax string to the 'dot' process stdin.
+        process
+            .stdin
+            .as_mut()
+            .map(|s| s.write_all(diagram.as_bytes()));
+
+        // Check the graphviz return status to see if it was successful.
+        match process.wait() {
+            Ok(status) => {
+                if !status.success() {
+                    panic!("'dot' failed to run. Are you sure graphviz is installed?");
+                }
+            }
+            Err(_) => panic!("'dot' failed to run. Are you sure graphviz is installed?"),
+        }
+    }
+
+    // The exports are written to `OUT_DIR`, which is only set for crates with a build script.
+    #[cfg(feature = "export")]
+    if let Some(directory) = std::env::var_os("OUT_DIR") {
+        use std::hash::{Hash, Hasher};
+
+        // Write the definition as SCXML and JSON for external tooling.
+        let json = export::generate_json(sm);
+        let export_name = if let Some(name) = &sm.name {
+            name.to_string()
+        } else {
+            let mut export_hasher = std::collections::hash_map::DefaultHasher::new();
+            json.hash(&mut export_hasher);
+            format!("smlang{:010x}", export_hasher.finish())
+        };
+
+        let write = |extension: &str, contents: String| {
+            let path = std::path::Path::new(&directory)
+                .join(format!("statemachine_{export_name}.{extension}"));
+            std::fs::write(&path, contents).map_err(|error| {
+                syn::Error::new(
+                    proc_macro2::Span::call_site(),
+                    format!(
+                        "Failed to write the export of the state machine to `{}`: {}",
+                        path.display(),
+                        error
+                    ),
+                )
+            })
+        };
+        write("json", json)?;
+        write("scxml", export::generate_scxml(sm))?;
+    }
+
+    Ok(codegen::generate_code(sm))
+}
@@ -11,6 +11,12 @@ pub struct DataDefinitions {
     pub lifetimes: HashMap<String, Lifetimes>,
 }
 
+impl Default for DataDefinitions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl DataDefinitions {
     pub fn new() -> Self {
         Self {
@@ -1,5 +1,6 @@
 use crate::parser::transition::GuardExpression;
-use crate::parser::AsyncIdent;
+use crate::parser::{AsyncIdent, Origin};
+use proc_macro2::Span;
 use syn::{parenthesized, parse, spanned::Spanned, token, Ident, Token, Type};
 
 #[derive(Debug, Clone)]
@@ -8,18 +9,36 @@ pub struct Event {
     pub data_type: Option<Type>,
 }
 
+/// The transitions of an event in a state.
 #[derive(Debug)]
 pub struct EventMapping {
+    /// The state the transitions leave.
     pub in_state: Ident,
+    /// The event which triggers the transitions.
     pub event: Ident,
+    /// The transitions in the order they are defined, the first one whose guard passes is taken.
     pub transitions: Vec<Transition>,
 }
 
+/// A single transition of an [`EventMapping`].
 #[derive(Debug)]
 pub struct Transition {
+    /// The guard which must pass for the transition to be taken.
     pub guard: Option<GuardExpression>,
+    /// The action run during the transition.
     pub action: Option<AsyncIdent>,
+    /// The state entered, which is the input state for internal transitions and
+    /// [`HISTORY_STATE`](crate::parser::output_state::HISTORY_STATE) for the history state.
     pub out_state: Ident,
+    /// The state entered instead when the guard or action fails, from `! State`.
+    pub error_state: Option<Ident>,
+    /// Position of the transition in the DSL, transitions expanded from a wildcard share the
+    /// position of the wildcard.
+    pub index: usize,
+    /// Span of the input state as written, which is the `_` for wildcard transitions.
+    pub in_state_span: Span,
+    /// Where the transition was read from, when the DSL is read from a file.
+    pub origin: Origin,
 }
 
 impl parse::Parse for Event {
@@ -0,0 +1,622 @@
+pub mod data;
+pub mod event;
+pub mod input_state;
+pub mod lifetimes;
+pub mod output_state;
+pub mod state;
+pub mod state_machine;
+pub mod transition;
+
+use data::DataDefinitions;
+use event::EventMapping;
+use state_machine::{ActionFailure, LintLevel, StateMachine};
+
+use input_state::InputState;
+use proc_macro2::{Span, TokenStream};
+use state::StateDeclaration;
+
+use crate::parser::event::Transition;
+use std::collections::{hash_map, HashMap};
+use std::fmt;
+use syn::{parse, token, Attribute, Generics, Ident, Token, Type, Visibility};
+use transition::StateTransition;
+pub type TransitionMap = HashMap<String, HashMap<String, EventMapping>>;
+
+/// Where an entry of the DSL was read from, as `file:line:column`, for state machines read from a
+/// file. The tokens parsed from a file all have the span of the macro invocation, so errors and
+/// warnings are prefixed with the origin of the entry they belong to instead.
+pub type Origin = Option<String>;
+
+/// Prefixes the messages of `error` with `origin`.
+pub fn locate(error: parse::Error, origin: &Origin) -> parse::Error {
+    let origin = match origin {
+        Some(origin) => origin,
+        None => return error,
+    };
+    error
+        .into_iter()
+        .map(|error| parse::Error::new(error.span(), format!("{}: {}", origin, error)))
+        .reduce(|mut errors, error| {
+            errors.combine(error);
+            errors
+        })
+        .unwrap()
+}
+
+/// Prefixes `message` with `origin`.
+pub fn locate_message(message: String, origin: &Origin) -> String {
+    match origin {
+        Some(origin) => format!("{}: {}", origin, message),
+        None => message,
+    }
+}
+
+/// The name of a guard or action, which is called with `.await` if it is async.
+#[derive(Debug, Clone)]
+pub struct AsyncIdent {
+    pub ident: Ident,
+    pub is_async: bool,
+    /// `Some(false)` for guards and actions marked infallible with `!`, `Some(true)` for those
+    /// marked fallible with `?`, otherwise `None` for the default set by `fallible:`.
+    pub fallible: Option<bool>,
+}
+impl AsyncIdent {
+    /// Whether the guard or action returns a `Result`, with `default` from `fallible:`.
+    pub fn is_fallible(&self, default: bool) -> bool {
+        self.fallible.unwrap_or(default)
+    }
+
+    pub fn to_token_stream<F>(&self, visit: &mut F) -> TokenStream
+    where
+        F: FnMut(&AsyncIdent) -> TokenStream,
+    {
+        visit(self)
+    }
+}
+impl parse::Parse for AsyncIdent {
+    fn parse(input: parse::ParseStream) -> syn::Result<Self> {
+        let is_async = input.parse::<token::Async>().is_ok();
+        let ident: Ident = input.parse()?;
+        // A `!` followed by a state is the error state of the transition
+        let fallible = if input.peek(Token![!]) && !input.peek2(Ident) {
+            input.parse::<Token![!]>()?;
+            Some(false)
+        } else if input.parse::<Token![?]>().is_ok() {
+            Some(true)
+        } else {
+            None
+        };
+        Ok(AsyncIdent {
+            ident,
+            is_async,
+            fallible,
+        })
+    }
+}
+impl fmt::Display for AsyncIdent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_async {
+            write!(f, "{}().await", self.ident)
+        } else {
+            write!(f, "{}()", self.ident)
+        }
+    }
+}
+
+/// A state machine whose definition has been checked for consistency, created from the parsed
+/// DSL with [`ParsedStateMachine::new`].
+///
+/// States and events are keyed by their name.
+#[derive(Debug)]
+pub struct ParsedStateMachine {
+    /// The `name:` prefixed to the generated types.
+    pub name: Option<Ident>,
+    /// The `states_attr:` attributes of the `States` enum.
+    pub states_attr: Vec<Attribute>,
+    /// The `events_attr:` attributes of the `Events` enum.
+    pub events_attr: Vec<Attribute>,
+    /// The `machine_attr:` attributes of the `StateMachine` struct.
+    pub machine_attr: Vec<Attribute>,
+    /// The `error_attr:` attributes of the `Error` enum, replacing the default
+    /// `#[derive(Debug, PartialEq)]`.
+    pub error_attr: Option<Vec<Attribute>>,
+    /// The `temporary_context:` type passed to `process_event`.
+    pub temporary_context_type: Option<Type>,
+    /// Whether guards and actions return the user's `Error` type, from `custom_error: true`.
+    pub custom_error: bool,
+    /// Whether guards and actions return a `Result` unless marked otherwise, from `fallible:`.
+    pub fallible: bool,
+    /// The `on_error:` state entered when a guard or action fails, unless the transition has its
+    /// own error state.
+    pub on_error: Option<Ident>,
+    /// What happens when the action of a transition fails, from `action_failure:`.
+    pub action_failure: ActionFailure,
+    /// Whether the errors carry the state and event they occurred in, from
+    /// `detailed_errors: true`.
+    pub detailed_errors: bool,
+    /// All states used in transitions.
+    pub states: HashMap<String, Ident>,
+    /// The state marked with `*`.
+    pub starting_state: Ident,
+    /// The data types of states.
+    pub state_data: DataDefinitions,
+    /// All events used in transitions.
+    pub events: HashMap<String, Ident>,
+    /// The data types of events.
+    pub event_data: DataDefinitions,
+    /// The transitions, keyed by input state and then event. Wildcard transitions are expanded
+    /// into the states they apply to.
+    pub states_events_mapping: HashMap<String, HashMap<String, EventMapping>>,
+    /// Whether the entry and exit hooks are async, from `entry_exit_async: true`.
+    pub entry_exit_async: bool,
+    /// Whether the futures of the async functions must be `Send`, from `async_send: true`.
+    pub async_send: bool,
+    /// The `states:` declarations of entry, exit and do-activities and final states.
+    pub state_declarations: HashMap<String, StateDeclaration>,
+    /// The `lints:` level of the reachability lints.
+    pub lint_level: LintLevel,
+    /// The `generics:` type and const parameters of the generated types.
+    pub generics: Generics,
+    /// The `visibility:` of the generated items, `pub` by default.
+    pub visibility: Visibility,
+    /// The `module:` the generated items are wrapped in.
+    pub module: Option<Ident>,
+    /// Where the transitions using the state idents of `states` were read from.
+    pub state_origins: HashMap<String, Origin>,
+    /// Where the transitions using the event idents of `events` were read from.
+    pub event_origins: HashMap<String, Origin>,
+}
+
+/// Accumulates errors, so all problems in the state machine are reported at once.
+#[derive(Default)]
+pub struct Errors(Option<parse::Error>);
+
+impl Errors {
+    pub fn push(&mut self, error: parse::Error) {
+        match &mut self.0 {
+            Some(errors) => errors.combine(error),
+            None => self.0 = Some(error),
+        }
+    }
+
+    /// Records an error of the DSL entry read from `origin`.
+    pub fn push_at(&mut self, origin: &Origin, error: parse::Error) {
+        self.push(locate(error, origin));
+    }
+
+    /// Records the error of a failed result, returning the value otherwise.
+    pub fn check<T>(&mut self, result: parse::Result<T>) -> Option<T> {
+        result.map_err(|error| self.push(error)).ok()
+    }
+
+    /// Records the error of a failed result of the DSL entry read from `origin`, returning the
+    /// value otherwise.
+    pub fn check_at<T>(&mut self, origin: &Origin, result: parse::Result<T>) -> Option<T> {
+        result.map_err(|error| self.push_at(origin, error)).ok()
+    }
+
+    pub fn finish(self) -> parse::Result<()> {
+        self.0.map_or(Ok(()), Err)
+    }
+}
+
+// helper function for adding a transition to a transition event map
+fn add_transition(
+    transition: &StateTransition,
+    index: usize,
+    in_state_span: Span,
+    transition_map: &mut TransitionMap,
+    state_data: &DataDefinitions,
+) -> Result<(), parse::Error> {
+    let p = transition_map
+        .get_mut(&transition.in_state.ident.to_string())
+        .unwrap();
+
+    let new_transition = Transition {
+        guard: transition.guard.clone(),
+        action: transition.action.clone(),
+        out_state: transition.out_state.ident.clone(),
+        error_state: transition.error_state.clone(),
+        index,
+        in_state_span,
+        origin: transition.origin.clone(),
+    };
+
+    match p.entry(transition.event.ident.to_string()) {
+        hash_map::Entry::Vacant(entry) => {
+            let mapping = EventMapping {
+                in_state: transition.in_state.ident.clone(),
+                event: transition.event.ident.clone(),
+                transitions: vec![new_transition],
+            };
+            entry.insert(mapping);
+        }
+        hash_map::Entry::Occupied(mut entry) => {
+            let mapping = entry.get_mut();
+            mapping.transitions.push(new_transition);
+        }
+    }
+
+    // Check for actions when states have data a
+    if state_data
+        .data_types
+        .contains_key(&transition.out_state.ident.to_string())
+    {
+        // This transition goes to a state that has data associated, check so it has an
+        // action
+
+        if transition.action.is_none() {
+            return Err(parse::Error::new(
+                transition.out_state.ident.span(),
+                "This state has data associated, but not action is define here to provide it.",
+            ));
+        }
+    }
+    Ok(())
+}
+
+impl ParsedStateMachine {
+    /// Checks if any transition targets the history state.
+    pub fn has_history(&self) -> bool {
+        self.states_events_mapping.values().any(|event_mappings| {
+            event_mappings.values().any(|event_mapping| {
+                event_mapping
+                    .transitions
+                    .iter()
+                    .any(|transition| transition.out_state == output_state::HISTORY_STATE)
+            })
+        })
+    }
+
+    /// Checks the parsed DSL, returning all errors found.
+    pub fn new(mut sm: StateMachine) -> parse::Result<Self> {
+        // Derive out_state for internal non-wildcard transitions
+        for transition in sm.transitions.iter_mut() {
+            if transition.out_state.internal_transition && !transition.in_state.wildcard {
+                transition.out_state.ident = transition.in_state.ident.clone();
+                transition
+                    .out_state
+                    .data_type
+                    .clone_from(&transition.in_state.data_type);
+                transition.out_state.internal_transition = false;
+            }
+        }
+
+        let mut errors = Errors::default();
+
+        // Check the initial state definition
+        let mut starting_transitions_iter = sm.transitions.iter().filter(|sm| sm.in_state.start);
+
+        let starting_state = match starting_transitions_iter.next() {
+            Some(starting_transition) => {
+                if let Some(duplicate) = starting_transitions_iter.next() {
+                    let mut error = locate(
+                        parse::Error::new(
+                            duplicate.in_state.ident.span(),
+                            "More than one starting state defined (indicated with *), remove duplicates.",
+                        ),
+                        &duplicate.origin,
+                    );
+                    error.combine(locate(
+                        parse::Error::new(
+                            starting_transition.in_state.ident.span(),
+                            "The first starting state is defined here.",
+                        ),
+                        &starting_transition.origin,
+                    ));
+                    errors.push(error);
+                }
+
+                // Extract the starting state
+                starting_transition.in_state.ident.clone()
+            }
+            None => {
+                errors.push(parse::Error::new(
+                    Span::call_site(),
+                    "No starting state defined, indicate the starting state with a *.",
+                ));
+                // Checking the rest of the machine continues with a placeholder, as the missing
+                // starting state is reported together with the other errors
+                Ident::new("__SmlangNoStartingState", Span::call_site())
+            }
+        };
+
+        let mut states = HashMap::new();
+        let mut state_origins = HashMap::new();
+        let mut state_data = DataDefinitions::new();
+        let mut events = HashMap::new();
+        let mut event_origins = HashMap::new();
+        let mut event_data = DataDefinitions::new();
+        let mut states_events_mapping = TransitionMap::new();
+
+        for transition in sm.transitions.iter() {
+            // The history pseudo-state can only be a target
+            if transition.in_state.ident == output_state::HISTORY_STATE {
+                errors.push_at(
+                    &transition.origin,
+                    parse::Error::new(
+                    transition.in_state.ident.span(),
+                    "`H` is reserved for the history state, which can only be used as an output state.",
+                    ),
+                );
+                continue;
+            }
+
+            // Collect states
+            let in_state_name = transition.in_state.ident.to_string();
+            if !transition.in_state.wildcard {
+                states.insert(in_state_name.clone(), transition.in_state.ident.clone());
+                state_origins.insert(in_state_name.clone(), transition.origin.clone());
+                errors.check_at(
+                    &transition.origin,
+                    state_data
+                        .collect(in_state_name.clone(), transition.in_state.data_type.clone()),
+                );
+            }
+            if !transition.out_state.internal_transition && !transition.out_state.history {
+                let out_state_name = transition.out_state.ident.to_string();
+                states.insert(out_state_name.clone(), transition.out_state.ident.clone());
+                state_origins.insert(out_state_name.clone(), transition.origin.clone());
+                errors.check_at(
+                    &transition.origin,
+                    state_data.collect(
+                        out_state_name.clone(),
+                        transition.out_state.data_type.clone(),
+                    ),
+                );
+            }
+
+            // Collect events
+            let event_name = transition.event.ident.to_string();
+            events.insert(event_name.clone(), transition.event.ident.clone());
+            event_origins.insert(event_name.clone(), transition.origin.clone());
+            errors.check_at(
+                &transition.origin,
+                event_data.collect(event_name.clone(), transition.event.data_type.clone()),
+            );
+
+            // add input and output states to the mapping HashMap
+            if !transition.in_state.wildcard {
+                states_events_mapping.insert(transition.in_state.ident.to_string(), HashMap::new());
+            }
+            if !transition.out_state.internal_transition && !transition.out_state.history {
+                states_events_mapping
+                    .insert(transition.out_state.ident.to_string(), HashMap::new());
+            }
+        }
+
+        // Collect the state declarations, these may only refer to states used in transitions
+        let mut state_declarations = HashMap::new();
+        for declaration in sm.state_declarations {
+            if let (Some(activity), Some(_)) = (&declaration.activity, &sm.temporary_context_type) {
+                errors.push_at(
+                    &declaration.origin,
+                    parse::Error::new(
+                        activity.ident.span(),
+                        "Do-activities cannot be used together with a temporary context.",
+                    ),
+                );
+            }
+
+            let name = declaration.ident.to_string();
+            if !states.contains_key(&name) {
+                errors.push_at(
+                    &declaration.origin,
+                    parse::Error::new(
+                        declaration.ident.span(),
+                        format!(
+                            "State `{}` is declared but not used in any transition.",
+                            name
+                        ),
+                    ),
+                );
+                continue;
+            }
+
+            match state_declarations.entry(name) {
+                hash_map::Entry::Vacant(entry) => {
+                    entry.insert(declaration);
+                }
+                hash_map::Entry::Occupied(_) => {
+                    errors.push_at(
+                        &declaration.origin,
+                        parse::Error::new(
+                            declaration.ident.span(),
+                            "State declared multiple times, merge the declarations.",
+                        ),
+                    );
+                }
+            }
+        }
+
+        // The states entered when a guard or action fails
+        let on_error_origin = sm.origins.get("on_error").cloned();
+        let error_states = sm
+            .on_error
+            .iter()
+            .map(|state| (state, &on_error_origin))
+            .chain(sm.transitions.iter().filter_map(|transition| {
+                Some((transition.error_state.as_ref()?, &transition.origin))
+            }));
+        for (state, origin) in error_states {
+            if !states.contains_key(&state.to_string()) {
+                errors.push_at(origin, parse::Error::new(
+                    state.span(),
+                    format!(
+                        "State `{}` entered when a guard or action fails is not used in any transition.",
+                        state
+                    ),
+                ));
+            }
+        }
+
+        // The state entered when an action fails is constructed without data
+        if let ActionFailure::Enter(state) = &sm.action_failure {
+            let origin = sm.origins.get("action_failure").cloned();
+            let name = state.to_string();
+            if !states.contains_key(&name) {
+                errors.push_at(
+                    &origin,
+                    parse::Error::new(
+                        state.span(),
+                        format!(
+                        "State `{}` entered when an action fails is not used in any transition.",
+                        name
+                    ),
+                    ),
+                );
+            } else if state_data.data_types.contains_key(&name) {
+                errors.push_at(
+                    &origin,
+                    parse::Error::new(
+                        state.span(),
+                        format!(
+                            "State `{}` entered when an action fails cannot have data.",
+                            name
+                        ),
+                    ),
+                );
+            }
+        }
+
+        for (index, transition) in sm.transitions.iter().enumerate() {
+            // already reported above
+            if transition.in_state.ident == output_state::HISTORY_STATE {
+                continue;
+            }
+
+            // if input state is a wildcard, we need to add this transition for all states
+            if transition.in_state.wildcard {
+                let mut transition_added = false;
+
+                for (name, in_state) in &states {
+                    // skip already set input state
+                    let p = states_events_mapping
+                        .get_mut(&in_state.to_string())
+                        .unwrap();
+
+                    if p.contains_key(&transition.event.ident.to_string()) {
+                        continue;
+                    }
+
+                    // final states never process events
+                    if state_declarations
+                        .get(name)
+                        .is_some_and(|declaration| declaration.is_final)
+                    {
+                        continue;
+                    }
+
+                    // create a new input state from wildcard
+                    let in_state = InputState {
+                        start: false,
+                        wildcard: false,
+                        ident: in_state.clone(),
+                        data_type: state_data.data_types.get(name).cloned(),
+                    };
+
+                    // create the transition
+                    let mut out_state = transition.out_state.clone();
+                    if out_state.internal_transition {
+                        out_state.ident = in_state.ident.clone();
+                        out_state.data_type.clone_from(&in_state.data_type);
+                    }
+                    let wildcard_transition = StateTransition {
+                        in_state,
+                        event: transition.event.clone(),
+                        guard: transition.guard.clone(),
+                        action: transition.action.clone(),
+                        out_state,
+                        error_state: transition.error_state.clone(),
+                        origin: transition.origin.clone(),
+                    };
+
+                    // add the wildcard transition to the transition map, keeping the span of
+                    // the wildcard so errors point at the line which caused them
+                    let result = add_transition(
+                        &wildcard_transition,
+                        index,
+                        transition.in_state.ident.span(),
+                        &mut states_events_mapping,
+                        &state_data,
+                    );
+
+                    transition_added = true;
+
+                    // The error would be the same for all states
+                    if errors.check_at(&transition.origin, result).is_none() {
+                        break;
+                    }
+                }
+
+                // No transitions were added by expanding the wildcard,
+                // so emit an error to the user
+                if !transition_added {
+                    errors.push_at(
+                        &transition.origin,
+                        parse::Error::new(
+                            transition.in_state.ident.span(),
+                            "Wildcard has no effect",
+                        ),
+                    );
+                }
+            } else {
+                if state_declarations
+                    .get(&transition.in_state.ident.to_string())
+                    .is_some_and(|declaration| declaration.is_final)
+                {
+                    errors.push_at(
+                        &transition.origin,
+                        parse::Error::new(
+                            transition.in_state.ident.span(),
+                            "Final states cannot have outgoing transitions.",
+                        ),
+                    );
+                    continue;
+                }
+
+                errors.check_at(
+                    &transition.origin,
+                    add_transition(
+                        transition,
+                        index,
+                        transition.in_state.ident.span(),
+                        &mut states_events_mapping,
+                        &state_data,
+                    ),
+                );
+            }
+        }
+
+        errors.finish()?;
+
+        Ok(ParsedStateMachine {
+            name: sm.name,
+            states_attr: sm.states_attr,
+            events_attr: sm.events_attr,
+            machine_attr: sm.machine_attr,
+            error_attr: sm.error_attr,
+            temporary_context_type: sm.temporary_context_type,
+            custom_error: sm.custom_error,
+            fallible: sm.fallible,
+            on_error: sm.on_error,
+            action_failure: sm.action_failure,
+            detailed_errors: sm.detailed_errors,
+            states,
+            starting_state,
+            state_data,
+            events,
+            event_data,
+            states_events_mapping,
+            entry_exit_async: sm.entry_exit_async,
+            async_send: sm.async_send,
+            state_declarations,
+            lint_level: sm.lint_level,
+            generics: sm.generics,
+            visibility: sm.visibility,
+            module: sm.module,
+            state_origins,
+            event_origins,
+        })
+    }
+}
@@ -1,10 +1,14 @@
 use proc_macro2::Span;
 use syn::{parenthesized, parse, spanned::Spanned, token, Ident, Token, Type};
 
+/// The name of the history pseudo-state, which resolves to the most recently exited state.
+pub const HISTORY_STATE: &str = "H";
+
 #[derive(Debug, Clone)]
 pub struct OutputState {
     pub ident: Ident,
     pub internal_transition: bool,
+    pub history: bool,
     pub data_type: Option<Type>,
 }
 
@@ -17,9 +21,22 @@ impl parse::Parse for OutputState {
                 let underscore = input.parse::<Token![_]>()?;
                 (true, underscore.into())
             } else {
-                (false, input.parse()?)
+                (false, input.parse::<Ident>()?)
             };
 
+            // Check for the history pseudo-state, `H*` (deep history) is accepted as well but
+            // is equivalent to `H` as there are no composite states
+            let history = !internal_transition && ident == HISTORY_STATE;
+            if history {
+                let _ = input.parse::<Token![*]>();
+                if input.peek(token::Paren) {
+                    return Err(parse::Error::new(
+                        input.span(),
+                        "The history state restores the data of the state, it cannot have data associated with it.",
+                    ));
+                }
+            }
+
             // Possible type on the output state
             let data_type = if !internal_transition && input.peek(token::Paren) {
                 let content;
@@ -50,6 +67,7 @@ impl parse::Parse for OutputState {
             Ok(Self {
                 ident,
                 internal_transition,
+                history,
                 data_type,
             })
         } else {
@@ -57,6 +75,7 @@ impl parse::Parse for OutputState {
             Ok(Self {
                 ident: Ident::new("_", Span::call_site()),
                 internal_transition: true,
+                history: false,
                 data_type: None,
             })
         }
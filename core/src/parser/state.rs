@@ -0,0 +1,194 @@
+use super::{AsyncIdent, Origin};
+use syn::{braced, ext::IdentExt, parse, Ident, Token};
+
+#[derive(Debug, Clone)]
+pub struct StateDeclaration {
+    pub ident: Ident,
+    pub entry: Option<AsyncIdent>,
+    pub exit: Option<AsyncIdent>,
+    pub activity: Option<AsyncIdent>,
+    pub is_final: bool,
+    /// Where the declaration was read from, when the DSL is read from a file.
+    pub origin: Origin,
+}
+
+impl parse::Parse for StateDeclaration {
+    fn parse(input: parse::ParseStream) -> syn::Result<Self> {
+        let ident: Ident = input.parse()?;
+
+        let mut declaration = StateDeclaration {
+            ident,
+            entry: None,
+            exit: None,
+            activity: None,
+            is_final: false,
+            origin: None,
+        };
+
+        let content;
+        braced!(content in input);
+        loop {
+            if content.is_empty() {
+                break;
+            }
+
+            // `do` and `final` are keywords, so they have to be parsed as any identifier
+            let kind = Ident::parse_any(&content)?;
+            let slot = match kind.to_string().as_str() {
+                "final" => {
+                    declaration.is_final = true;
+                    None
+                }
+                "entry" => Some(&mut declaration.entry),
+                "exit" => Some(&mut declaration.exit),
+                "do" => Some(&mut declaration.activity),
+                keyword => {
+                    return Err(parse::Error::new(
+                        kind.span(),
+                        format!(
+                        "Unknown state property {}. Supported properties: [\"entry\", \"exit\", \"do\", \"final\"]",
+                        keyword
+                    ),
+                    ))
+                }
+            };
+
+            if let Some(slot) = slot {
+                if slot.is_some() {
+                    return Err(parse::Error::new(
+                        kind.span(),
+                        format!("Only one `{}` action can be declared per state.", kind),
+                    ));
+                }
+
+                content.parse::<Token![/]>()?;
+                let action: AsyncIdent = content.parse()?;
+                if action.fallible.is_some() {
+                    return Err(parse::Error::new(
+                        action.ident.span(),
+                        "Entry and exit actions and activities never return a `Result`, only guards and transition actions can be marked with `!` or `?`.",
+                    ));
+                }
+
+                // Activities run concurrently with the event processing, which requires them to
+                // be futures
+                if kind == "do" && !action.is_async {
+                    return Err(parse::Error::new(
+                        action.ident.span(),
+                        "Do-activities must be async, e.g. `do / async poll`.",
+                    ));
+                }
+
+                *slot = Some(action);
+            }
+
+            // No comma at end of line, no more properties
+            if content.is_empty() {
+                break;
+            }
+
+            if content.parse::<Token![,]>().is_err() {
+                break;
+            };
+        }
+
+        if !content.is_empty() {
+            return Err(content.error("Expected `,` between state properties."));
+        }
+
+        if let (true, Some(exit)) = (declaration.is_final, &declaration.exit) {
+            return Err(parse::Error::new(
+                exit.ident.span(),
+                "Final states are never exited, so they cannot have an exit action.",
+            ));
+        }
+
+        Ok(declaration)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    #[test]
+    fn entry_and_exit() {
+        let state: StateDeclaration = parse_quote! {
+            Connected { entry / start_timer, exit / async stop_timer }
+        };
+
+        assert_eq!(state.ident, "Connected");
+
+        let entry = state.entry.unwrap();
+        assert_eq!(entry.ident, "start_timer");
+        assert!(!entry.is_async);
+
+        let exit = state.exit.unwrap();
+        assert_eq!(exit.ident, "stop_timer");
+        assert!(exit.is_async);
+    }
+
+    #[test]
+    fn activity() {
+        let state: StateDeclaration = parse_quote! {
+            Connected { do / async poll_socket }
+        };
+
+        let activity = state.activity.unwrap();
+        assert_eq!(activity.ident, "poll_socket");
+        assert!(activity.is_async);
+    }
+
+    #[test]
+    #[should_panic(expected = "Do-activities must be async")]
+    fn sync_activity() {
+        let _: StateDeclaration = parse_quote! {
+            Connected { do / poll_socket }
+        };
+    }
+
+    #[test]
+    fn final_state() {
+        let state: StateDeclaration = parse_quote! {
+            Done { final, entry / report }
+        };
+
+        assert!(state.is_final);
+        assert!(state.entry.is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "Final states are never exited")]
+    fn final_state_with_exit() {
+        let _: StateDeclaration = parse_quote! {
+            Done { final, exit / report }
+        };
+    }
+
+    #[test]
+    fn empty() {
+        let state: StateDeclaration = parse_quote! {
+            Idle {}
+        };
+
+        assert!(state.entry.is_none());
+        assert!(state.exit.is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Only one `entry` action can be declared per state.")]
+    fn duplicate_entry() {
+        let _: StateDeclaration = parse_quote! {
+            Idle { entry / a, entry / b }
+        };
+    }
+
+    #[test]
+    #[should_panic(expected = "Unknown state property")]
+    fn unknown_property() {
+        let _: StateDeclaration = parse_quote! {
+            Idle { during / a }
+        };
+    }
+}
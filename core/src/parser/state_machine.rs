@@ -0,0 +1,407 @@
+use super::state::StateDeclaration;
+use super::transition::{StateTransition, StateTransitions};
+use super::Errors;
+use proc_macro2::TokenTree;
+use std::collections::HashMap;
+use syn::{
+    braced, parse, spanned::Spanned, token, Attribute, GenericParam, Generics, Ident, Token, Type,
+    Visibility,
+};
+
+/// What happens when the action of a transition fails, set with `action_failure:`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ActionFailure {
+    /// The exit hooks of the state run before the action, and the machine stays in the state when
+    /// the action fails.
+    ExitFirst,
+    /// The action runs before the exit hooks, so nothing else ran when it fails.
+    ActionFirst,
+    /// The entry hooks of the state run again when the action fails after they were exited.
+    Reenter,
+    /// The machine enters the given state when the action fails.
+    Enter(Ident),
+}
+
+/// How problems found by the analysis of the state machine graph are reported.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LintLevel {
+    Allow,
+    Warn,
+    Deny,
+}
+
+#[derive(Debug)]
+pub struct StateMachine {
+    pub temporary_context_type: Option<Type>,
+    pub custom_error: bool,
+    pub detailed_errors: bool,
+    pub fallible: bool,
+    pub action_failure: ActionFailure,
+    pub on_error: Option<Ident>,
+    pub transitions: Vec<StateTransition>,
+    pub name: Option<Ident>,
+    pub states_attr: Vec<Attribute>,
+    pub events_attr: Vec<Attribute>,
+    pub machine_attr: Vec<Attribute>,
+    pub error_attr: Option<Vec<Attribute>>,
+    pub entry_exit_async: bool,
+    pub async_send: bool,
+    pub state_declarations: Vec<StateDeclaration>,
+    pub lint_level: LintLevel,
+    pub generics: Generics,
+    pub visibility: Visibility,
+    pub module: Option<Ident>,
+    /// Where the options were read from, keyed by their keyword, when the DSL is read from a
+    /// file.
+    pub origins: HashMap<String, String>,
+}
+
+impl Default for StateMachine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StateMachine {
+    pub fn new() -> Self {
+        StateMachine {
+            temporary_context_type: None,
+            custom_error: false,
+            detailed_errors: false,
+            fallible: true,
+            action_failure: ActionFailure::ExitFirst,
+            on_error: None,
+            transitions: Vec::new(),
+            name: None,
+            states_attr: Vec::new(),
+            events_attr: Vec::new(),
+            machine_attr: Vec::new(),
+            error_attr: None,
+            entry_exit_async: false,
+            async_send: false,
+            state_declarations: Vec::new(),
+            lint_level: LintLevel::Warn,
+            generics: Generics::default(),
+            visibility: Visibility::Public(Default::default()),
+            module: None,
+            origins: HashMap::new(),
+        }
+    }
+
+    pub fn add_transitions(&mut self, transitions: StateTransitions) {
+        for in_state in transitions.in_states {
+            let transition = StateTransition {
+                in_state,
+                event: transitions.event.clone(),
+                guard: transitions.guard.clone(),
+                action: transitions.action.clone(),
+                out_state: transitions.out_state.clone(),
+                error_state: transitions.error_state.clone(),
+                origin: None,
+            };
+            self.transitions.push(transition);
+        }
+    }
+
+    /// Parses an option, or a `states` or `transitions` block, recording the errors which do not
+    /// prevent parsing the following entries in `errors`.
+    pub fn parse_entry(
+        &mut self,
+        input: parse::ParseStream,
+        errors: &mut Errors,
+    ) -> parse::Result<()> {
+        match input.parse::<Ident>()?.to_string().as_str() {
+            "transitions" => {
+                input.parse::<Token![:]>()?;
+                if input.peek(token::Brace) {
+                    let content;
+                    braced!(content in input);
+                    loop {
+                        if content.is_empty() {
+                            break;
+                        }
+
+                        // Continue with the next transition after an error, so all
+                        // syntax errors are reported at once
+                        match content.parse::<StateTransitions>() {
+                            Ok(transitions) => self.add_transitions(transitions),
+                            Err(error) => {
+                                errors.push(error);
+                                skip_to_comma(&content)?;
+                            }
+                        }
+
+                        // No comma at end of line, no more transitions
+                        if content.is_empty() {
+                            break;
+                        }
+
+                        if content.parse::<Token![,]>().is_err() {
+                            break;
+                        };
+                    }
+                }
+            }
+            "states" => {
+                input.parse::<Token![:]>()?;
+                let content;
+                braced!(content in input);
+                loop {
+                    if content.is_empty() {
+                        break;
+                    }
+
+                    match content.parse::<StateDeclaration>() {
+                        Ok(declaration) => self.state_declarations.push(declaration),
+                        Err(error) => {
+                            errors.push(error);
+                            skip_to_comma(&content)?;
+                        }
+                    }
+
+                    // No comma at end of line, no more state declarations
+                    if content.is_empty() {
+                        break;
+                    }
+
+                    if content.parse::<Token![,]>().is_err() {
+                        break;
+                    };
+                }
+            }
+            "custom_error" => {
+                input.parse::<Token![:]>()?;
+                let custom_error: syn::LitBool = input.parse()?;
+                if custom_error.value {
+                    self.custom_error = true
+                }
+            }
+            "temporary_context" => {
+                input.parse::<Token![:]>()?;
+                let temporary_context_type: Type = input.parse()?;
+
+                // Check so the type is supported
+                match &temporary_context_type {
+                    Type::Array(_)
+                    | Type::Path(_)
+                    | Type::Ptr(_)
+                    | Type::Reference(_)
+                    | Type::Slice(_)
+                    | Type::Tuple(_) => (),
+                    _ => {
+                        return Err(parse::Error::new(
+                            temporary_context_type.span(),
+                            "This is an unsupported type for the temporary state.",
+                        ))
+                    }
+                }
+
+                // Store the temporary context type
+                self.temporary_context_type = Some(temporary_context_type);
+            }
+            "name" => {
+                input.parse::<Token![:]>()?;
+                self.name = Some(input.parse::<Ident>()?);
+            }
+
+            "fallible" => {
+                input.parse::<Token![:]>()?;
+                let fallible: syn::LitBool = input.parse()?;
+                self.fallible = fallible.value;
+            }
+
+            "on_error" => {
+                input.parse::<Token![:]>()?;
+                self.on_error = Some(input.parse()?);
+            }
+
+            "action_failure" => {
+                input.parse::<Token![:]>()?;
+                let policy: Ident = input.parse()?;
+                self.action_failure = match policy.to_string().as_str() {
+                    "exit_first" => ActionFailure::ExitFirst,
+                    "action_first" => ActionFailure::ActionFirst,
+                    "reenter" => ActionFailure::Reenter,
+                    "enter" => {
+                        let content;
+                        syn::parenthesized!(content in input);
+                        ActionFailure::Enter(content.parse()?)
+                    }
+                    _ => return Err(parse::Error::new(
+                        policy.span(),
+                        "Unknown action failure policy, expected one of `exit_first`, `action_first`, `reenter` or `enter(State)`.",
+                    )),
+                };
+            }
+
+            "detailed_errors" => {
+                input.parse::<Token![:]>()?;
+                let detailed_errors: syn::LitBool = input.parse()?;
+                if detailed_errors.value {
+                    self.detailed_errors = true;
+                }
+            }
+
+            "states_attr" => {
+                input.parse::<Token![:]>()?;
+                self.states_attr = Attribute::parse_outer(input)?;
+            }
+
+            "events_attr" => {
+                input.parse::<Token![:]>()?;
+                self.events_attr = Attribute::parse_outer(input)?;
+            }
+
+            "machine_attr" => {
+                input.parse::<Token![:]>()?;
+                self.machine_attr = Attribute::parse_outer(input)?;
+            }
+
+            "error_attr" => {
+                input.parse::<Token![:]>()?;
+                self.error_attr = Some(Attribute::parse_outer(input)?);
+            }
+
+            "entry_exit_async" => {
+                input.parse::<Token![:]>()?;
+                let entry_exit_async: syn::LitBool = input.parse()?;
+                if entry_exit_async.value {
+                    self.entry_exit_async = true;
+                }
+            }
+
+            "async_send" => {
+                input.parse::<Token![:]>()?;
+                let async_send: syn::LitBool = input.parse()?;
+                if async_send.value {
+                    self.async_send = true;
+                }
+            }
+
+            "lints" => {
+                input.parse::<Token![:]>()?;
+                let level: Ident = input.parse()?;
+                self.lint_level = match level.to_string().as_str() {
+                    "allow" => LintLevel::Allow,
+                    "warn" => LintLevel::Warn,
+                    "deny" => LintLevel::Deny,
+                    _ => {
+                        return Err(parse::Error::new(
+                            level.span(),
+                            "Unknown lint level, expected one of `allow`, `warn` or `deny`.",
+                        ))
+                    }
+                };
+            }
+
+            "generics" => {
+                input.parse::<Token![:]>()?;
+                let generics: Generics = input.parse()?;
+                for param in &generics.params {
+                    let default = match param {
+                        GenericParam::Lifetime(lifetime) => {
+                            errors.push(parse::Error::new(
+                                lifetime.span(),
+                                "Lifetimes are derived from the data types, only declare type and const parameters.",
+                            ));
+                            None
+                        }
+                        GenericParam::Type(param) => param.default.as_ref().map(Spanned::span),
+                        GenericParam::Const(param) => param.default.as_ref().map(Spanned::span),
+                    };
+                    if let Some(span) = default {
+                        errors.push(parse::Error::new(
+                            span,
+                            "Default values of generic parameters are not supported.",
+                        ));
+                    }
+                }
+                self.generics = generics;
+            }
+
+            "visibility" => {
+                input.parse::<Token![:]>()?;
+                self.visibility = input.parse()?;
+            }
+
+            "module" => {
+                input.parse::<Token![:]>()?;
+                self.module = Some(input.parse::<Ident>()?);
+            }
+
+            keyword => {
+                return Err(parse::Error::new(
+                    input.span(),
+                    format!(
+                        "Unknown keyword {}. Support keywords: [\"name\", \
+                            \"transitions\", \
+                            \"states\", \
+                            \"temporary_context\", \
+                            \"custom_error\", \
+                            \"detailed_errors\", \
+                            \"fallible\", \
+                            \"action_failure\", \
+                            \"on_error\", \
+                            \"states_attr\", \
+                            \"events_attr\", \
+                            \"machine_attr\", \
+                            \"error_attr\", \
+                            \"entry_exit_async\", \
+                            \"async_send\", \
+                            \"lints\", \
+                            \"generics\", \
+                            \"visibility\", \
+                            \"module\"
+                            ]",
+                        keyword
+                    ),
+                ))
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl parse::Parse for StateMachine {
+    fn parse(input: parse::ParseStream) -> parse::Result<Self> {
+        let mut statemachine = StateMachine::new();
+        let mut errors = Errors::default();
+
+        loop {
+            // If the last line ends with a comma this is true
+            if input.is_empty() {
+                break;
+            }
+
+            statemachine.parse_entry(input, &mut errors)?;
+
+            // No comma at end of line, no more transitions
+            if input.is_empty() {
+                break;
+            }
+
+            if input.parse::<Token![,]>().is_err() {
+                break;
+            };
+        }
+
+        errors.finish()?;
+        Ok(statemachine)
+    }
+}
+
+// Skips the remaining tokens of a list entry which failed to parse, up to the `,` separating it
+// from the next entry.
+fn skip_to_comma(input: parse::ParseStream) -> parse::Result<()> {
+    input.step(|cursor| {
+        let mut rest = *cursor;
+        while let Some((token, next)) = rest.token_tree() {
+            match token {
+                TokenTree::Punct(punct) if punct.as_char() == ',' => return Ok(((), rest)),
+                _ => rest = next,
+            }
+        }
+        Ok(((), rest))
+    })
+}
@@ -1,10 +1,9 @@
 use super::event::Event;
 use super::input_state::InputState;
 use super::output_state::OutputState;
-use super::AsyncIdent;
-use proc_macro2::TokenStream;
+use super::{AsyncIdent, Origin};
+use proc_macro2::{Span, TokenStream};
 use quote::quote;
-use std::fmt;
 use syn::{bracketed, parse, token, Ident, Token};
 
 #[derive(Debug, Clone)]
@@ -14,6 +13,9 @@ pub struct StateTransition {
     pub guard: Option<GuardExpression>,
     pub action: Option<AsyncIdent>,
     pub out_state: OutputState,
+    pub error_state: Option<Ident>,
+    /// Where the transition was read from, when the DSL is read from a file.
+    pub origin: Origin,
 }
 
 #[derive(Debug)]
@@ -23,6 +25,7 @@ pub struct StateTransitions {
     pub guard: Option<GuardExpression>,
     pub action: Option<AsyncIdent>,
     pub out_state: OutputState,
+    pub error_state: Option<Ident>,
 }
 
 impl parse::Parse for StateTransitions {
@@ -62,51 +65,54 @@ impl parse::Parse for StateTransitions {
 
         // Possible action
         let action = if input.parse::<Token![/]>().is_ok() {
-            let is_async = input.parse::<token::Async>().is_ok();
-            let action: Ident = input.parse()?;
-            Some(AsyncIdent {
-                ident: action,
-                is_async,
-            })
+            Some(input.parse()?)
         } else {
             None
         };
 
         let out_state: OutputState = input.parse()?;
 
+        // Possible state entered when the guard or action fails
+        let error_state = if input.parse::<Token![!]>().is_ok() {
+            Some(input.parse()?)
+        } else {
+            None
+        };
+
         Ok(Self {
             in_states,
             event,
             guard,
             action,
             out_state,
+            error_state,
         })
     }
 }
+/// A guard, or a boolean expression of guards, of a transition.
 #[derive(Debug, Clone)]
 pub enum GuardExpression {
+    /// A call of a guard, `guard` or `async guard`, optionally marked with `!` or `?`.
     Guard(AsyncIdent),
+    /// `!expression`
     Not(Box<GuardExpression>),
+    /// `(expression)`
     Group(Box<GuardExpression>),
+    /// `lhs && rhs`
     And(Box<GuardExpression>, Box<GuardExpression>),
+    /// `lhs || rhs`
     Or(Box<GuardExpression>, Box<GuardExpression>),
 }
-impl fmt::Display for GuardExpression {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+impl GuardExpression {
+    /// The span of the first guard in the expression.
+    pub fn span(&self) -> Span {
         match self {
-            GuardExpression::Guard(async_ident) => write!(f, "{}", async_ident),
-            GuardExpression::Not(expr) => write!(f, "!{}", expr),
-            GuardExpression::Group(expr) => write!(f, "({})", expr),
-            GuardExpression::And(lhs, rhs) => {
-                write!(f, "{} && {}", lhs, rhs)
-            }
-            GuardExpression::Or(lhs, rhs) => {
-                write!(f, "{} || {}", lhs, rhs)
-            }
+            GuardExpression::Guard(async_ident) => async_ident.ident.span(),
+            GuardExpression::Not(expr) | GuardExpression::Group(expr) => expr.span(),
+            GuardExpression::And(lhs, _) | GuardExpression::Or(lhs, _) => lhs.span(),
         }
     }
-}
-impl GuardExpression {
+
     pub fn to_token_stream<F>(&self, visit: &mut F) -> TokenStream
     where
         F: FnMut(&AsyncIdent) -> TokenStream,
@@ -200,25 +206,13 @@ fn parse_primary(input: parse::ParseStream) -> syn::Result<GuardExpression> {
         return Ok(GuardExpression::Group(Box::new(expr)));
     }
 
-    if input.peek(Token![async]) {
-        let _async: Token![async] = input.parse()?;
-        let ident: Ident = input.parse()?;
-        return Ok(GuardExpression::Guard(AsyncIdent {
-            ident,
-            is_async: true,
-        }));
-    }
-
-    let ident: Ident = input.parse()?;
-    Ok(GuardExpression::Guard(AsyncIdent {
-        ident,
-        is_async: false,
-    }))
+    Ok(GuardExpression::Guard(input.parse()?))
 }
 
 #[cfg(test)]
 mod test {
-    use crate::parser::transition::GuardExpression;
+    use crate::export::guard_string;
+    use crate::parser::transition::{visit_guards, GuardExpression};
     use syn::parse_str;
 
     #[test]
@@ -229,25 +223,44 @@ mod test {
     #[test]
     fn guard_expressions() -> Result<(), syn::Error> {
         for (guard_expression_str, expected) in vec![
-            ("guard", "guard()"),
-            ("async guard", "guard().await"),
-            ("async a || async b", "a().await || b().await"),
-            ("!guard", "!guard()"),
-            ("a && b", "a() && b()"),
-            ("a || b", "a() || b()"),
-            ("a || b || c", "a() || b() || c()"),
-            ("a || b && c || d", "a() || b() && c() || d()"),
-            ("(a || b) && (c || d)", "(a() || b()) && (c() || d())"),
-            ("a && b || c && d", "a() && b() || c() && d()"),
-            (
-                "a && ( !b && c ) || d && e",
-                "a() && (!b() && c()) || d() && e()",
-            ),
+            ("guard", "guard"),
+            ("async guard", "async guard"),
+            ("async a || async b", "async a || async b"),
+            ("!guard", "!guard"),
+            ("a && b", "a && b"),
+            ("a || b", "a || b"),
+            ("a || b || c", "a || b || c"),
+            ("a || b && c || d", "a || b && c || d"),
+            ("(a || b) && (c || d)", "(a || b) && (c || d)"),
+            ("a && b || c && d", "a && b || c && d"),
+            ("a && ( !b && c ) || d && e", "a && (!b && c) || d && e"),
         ] {
             let guard_expression: GuardExpression = parse_str(guard_expression_str)?;
-            assert_eq!(guard_expression.to_string(), expected);
+            assert_eq!(guard_string(&guard_expression), expected);
             println!("{:?}", guard_expression);
         }
         Ok(())
     }
+
+    #[test]
+    fn fallibility_markers() -> Result<(), syn::Error> {
+        let guard_expression: GuardExpression = parse_str("a! && !async b? || c")?;
+        assert_eq!(guard_string(&guard_expression), "a! && !async b? || c");
+
+        let mut guards = Vec::new();
+        visit_guards(&guard_expression, |guard| {
+            guards.push((guard.ident.to_string(), guard.fallible));
+            Ok(())
+        })?;
+        guards.sort();
+        assert_eq!(
+            guards,
+            [
+                ("a".to_string(), Some(false)),
+                ("b".to_string(), Some(true)),
+                ("c".to_string(), None)
+            ]
+        );
+        Ok(())
+    }
 }
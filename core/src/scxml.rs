@@ -0,0 +1,581 @@
+use crate::parser::transition::GuardExpression;
+use crate::parser::AsyncIdent;
+use proc_macro2::TokenStream;
+use roxmltree::{Document, Node};
+use syn::{parse, Ident, LitStr};
+
+/// Expands a state machine which is read from an SCXML document relative to
+/// `CARGO_MANIFEST_DIR`.
+pub fn expand(path: &LitStr) -> parse::Result<TokenStream> {
+    let (full_path, document) = crate::file::read(path)?;
+    let file = path.value();
+
+    let converted = convert(&document).map_err(|error| {
+        parse::Error::new(
+            path.span(),
+            format!("{}:{}: {}", file, error.line, error.message),
+        )
+    })?;
+
+    crate::file::expand_source(&converted.source, path, &full_path, |location| {
+        format!("{}:{}", file, converted.xml_line(location.line))
+    })
+}
+
+/// A problem in the SCXML document, which cannot be converted to the DSL.
+#[derive(Debug)]
+pub struct Error {
+    pub line: u32,
+    pub message: String,
+}
+
+/// A state machine in the DSL, converted from an SCXML document.
+pub struct Converted {
+    pub source: String,
+
+    // The line in the SCXML document of each line in the source
+    lines: Vec<u32>,
+}
+
+impl Converted {
+    fn push(&mut self, xml_line: u32, line: String) {
+        self.source.push_str(&line);
+        self.source.push('\n');
+        self.lines.push(xml_line);
+    }
+
+    /// The line in the SCXML document which a line of the source was converted from.
+    pub fn xml_line(&self, line: usize) -> u32 {
+        self.lines
+            .get(line.saturating_sub(1))
+            .or_else(|| self.lines.last())
+            .copied()
+            .unwrap_or(1)
+    }
+}
+
+struct Transition {
+    line: u32,
+    event: String,
+    guard: Option<String>,
+    action: Option<String>,
+    target: Option<String>,
+}
+
+struct State {
+    line: u32,
+    id: String,
+    is_final: bool,
+    entry: Option<String>,
+    exit: Option<String>,
+    transitions: Vec<Transition>,
+}
+
+/// Converts an SCXML document to the DSL, rejecting the constructs which the DSL cannot express.
+pub fn convert(document: &str) -> Result<Converted, Error> {
+    let document = Document::parse(document).map_err(|error| Error {
+        line: error.pos().row,
+        message: format!("Invalid SCXML document: {}", error),
+    })?;
+    let line = |node: Node| document.text_pos_at(node.range().start).row;
+    let error = |node: Node, message: String| Error {
+        line: line(node),
+        message,
+    };
+
+    let root = document.root_element();
+    if root.tag_name().name() != "scxml" {
+        return Err(error(
+            root,
+            format!(
+                "Expected an `<scxml>` root element, found `<{}>`.",
+                root.tag_name().name()
+            ),
+        ));
+    }
+
+    attributes(root, &["name", "initial", "version", "datamodel"])
+        .map_err(|message| error(root, message))?;
+
+    let mut states = Vec::new();
+    for node in root.children().filter(Node::is_element) {
+        let is_final = match node.tag_name().name() {
+            "state" => false,
+            "final" => true,
+            "parallel" => {
+                return Err(error(
+                    node,
+                    "Parallel states are not supported by smlang.".to_string(),
+                ))
+            }
+            tag => return Err(error(node, unsupported(tag))),
+        };
+
+        attributes(node, &["id"]).map_err(|message| error(node, message))?;
+        let id = identifier(node, "id").map_err(|message| error(node, message))?;
+        let mut state = State {
+            line: line(node),
+            id,
+            is_final,
+            entry: None,
+            exit: None,
+            transitions: Vec::new(),
+        };
+
+        for child in node.children().filter(Node::is_element) {
+            match child.tag_name().name() {
+                "transition" => {
+                    let transitions = convert_transition(child, line(child))
+                        .map_err(|message| error(child, message))?;
+                    state.transitions.extend(transitions);
+                }
+                kind @ ("onentry" | "onexit") => {
+                    let slot = if kind == "onentry" {
+                        &mut state.entry
+                    } else {
+                        &mut state.exit
+                    };
+                    if slot.is_some() {
+                        return Err(error(
+                            child,
+                            format!("Only one `<{}>` is supported per state.", kind),
+                        ));
+                    }
+                    attributes(child, &[]).map_err(|message| error(child, message))?;
+                    *slot = script(child).map_err(|message| error(child, message))?;
+                }
+                "state" | "parallel" | "final" | "history" | "initial" => {
+                    return Err(error(
+                        child,
+                        "Nested states are not supported by smlang.".to_string(),
+                    ))
+                }
+                tag => return Err(error(child, unsupported(tag))),
+            }
+        }
+
+        states.push(state);
+    }
+
+    // The initial state is either given, or the first state in the document
+    let initial = match root.attribute("initial") {
+        Some(initial) if initial.split_whitespace().count() > 1 => {
+            return Err(error(
+                root,
+                "Only a single initial state is supported by smlang.".to_string(),
+            ))
+        }
+        Some(initial) => initial.to_string(),
+        None => match states.first() {
+            Some(state) => state.id.clone(),
+            None => return Err(error(root, "The document contains no states.".to_string())),
+        },
+    };
+    match states.iter().find(|state| state.id == initial) {
+        None => {
+            return Err(error(
+                root,
+                format!("The initial state `{}` does not exist.", initial),
+            ))
+        }
+        Some(state) if state.transitions.is_empty() => {
+            return Err(Error {
+                line: state.line,
+                message: format!(
+                    "The initial state `{}` has no outgoing transitions, which smlang requires of the starting state.",
+                    initial
+                ),
+            })
+        }
+        Some(_) => (),
+    }
+
+    // States are only known to smlang through their transitions
+    for state in &states {
+        let targeted = states.iter().any(|other| {
+            other
+                .transitions
+                .iter()
+                .any(|transition| transition.target.as_ref() == Some(&state.id))
+        });
+        if state.transitions.is_empty() && !targeted {
+            return Err(Error {
+                line: state.line,
+                message: format!(
+                    "State `{}` is not connected to any transition, which smlang cannot express.",
+                    state.id
+                ),
+            });
+        }
+    }
+
+    let mut converted = Converted {
+        source: String::new(),
+        lines: Vec::new(),
+    };
+    let root_line = line(root);
+
+    if root.attribute("name").is_some() {
+        let name = identifier(root, "name").map_err(|message| error(root, message))?;
+        converted.push(root_line, format!("name: {},", name));
+    }
+
+    converted.push(root_line, "states: {".to_string());
+    for state in &states {
+        let mut properties = Vec::new();
+        if state.is_final {
+            properties.push("final".to_string());
+        }
+        if let Some(entry) = &state.entry {
+            properties.push(format!("entry / {}", entry));
+        }
+        if let Some(exit) = &state.exit {
+            properties.push(format!("exit / {}", exit));
+        }
+        if !properties.is_empty() {
+            converted.push(
+                state.line,
+                format!("    {} {{ {} }},", state.id, properties.join(", ")),
+            );
+        }
+    }
+    converted.push(root_line, "},".to_string());
+
+    converted.push(root_line, "transitions: {".to_string());
+    let mut starting_state_marked = false;
+    for state in &states {
+        for transition in &state.transitions {
+            let start = if state.id == initial && !starting_state_marked {
+                starting_state_marked = true;
+                "*"
+            } else {
+                ""
+            };
+            let guard = transition
+                .guard
+                .as_ref()
+                .map(|guard| format!(" [{}]", guard))
+                .unwrap_or_default();
+            let action = transition
+                .action
+                .as_ref()
+                .map(|action| format!(" / {}", action))
+                .unwrap_or_default();
+            let target = transition.target.as_deref().unwrap_or("_");
+
+            converted.push(
+                transition.line,
+                format!(
+                    "    {}{} + {}{}{} = {},",
+                    start, state.id, transition.event, guard, action, target
+                ),
+            );
+        }
+    }
+    converted.push(root_line, "}".to_string());
+
+    Ok(converted)
+}
+
+// Converts a `<transition>`, which results in a transition per event it is triggered by.
+fn convert_transition(node: Node, line: u32) -> Result<Vec<Transition>, String> {
+    attributes(node, &["event", "cond", "target"])?;
+    let events = node.attribute("event").ok_or_else(|| {
+        "Eventless transitions are not supported by smlang, add an `event` attribute.".to_string()
+    })?;
+
+    let guard = match node.attribute("cond") {
+        Some(cond) => {
+            syn::parse_str::<GuardExpression>(cond).map_err(|_| {
+                format!(
+                    "The condition `{}` is not supported, only guard names combined with `!`, `&&` and `||` can be used.",
+                    cond
+                )
+            })?;
+            Some(cond.to_string())
+        }
+        None => None,
+    };
+
+    // Transitions without a target do not leave the state, which is an internal transition
+    let target = match node.attribute("target") {
+        Some(target) if target.split_whitespace().count() > 1 => {
+            return Err("Transitions to multiple states are not supported by smlang.".to_string())
+        }
+        Some(_) => Some(identifier(node, "target")?),
+        None => None,
+    };
+
+    let action = script(node)?;
+
+    let mut transitions = Vec::new();
+    for event in events.split_whitespace() {
+        syn::parse_str::<Ident>(event).map_err(|_| {
+            format!(
+                "The event `{}` is not supported, events must be valid Rust identifiers.",
+                event
+            )
+        })?;
+
+        transitions.push(Transition {
+            line,
+            event: event.to_string(),
+            guard: guard.clone(),
+            action: action.clone(),
+            target: target.clone(),
+        });
+    }
+
+    if transitions.is_empty() {
+        return Err(
+            "Eventless transitions are not supported by smlang, add an `event` attribute."
+                .to_string(),
+        );
+    }
+
+    Ok(transitions)
+}
+
+// The executable content of transitions, `<onentry>` and `<onexit>` can only name the action to
+// call, in a `<script>` such as `<script>async open_door</script>`.
+fn script(node: Node) -> Result<Option<String>, String> {
+    let mut action = None;
+    for child in node.children().filter(Node::is_element) {
+        if child.tag_name().name() != "script" {
+            return Err(format!(
+                "`<{}>` is not supported by smlang, actions are named with `<script>action</script>`.",
+                child.tag_name().name()
+            ));
+        }
+        if action.is_some() {
+            return Err("Only a single `<script>` action is supported.".to_string());
+        }
+        attributes(child, &[])?;
+
+        let text = child.text().unwrap_or_default().trim();
+        syn::parse_str::<AsyncIdent>(text).map_err(|_| {
+            format!(
+                "The script `{}` is not supported, it must be the name of an action, optionally preceded by `async`.",
+                text
+            )
+        })?;
+        action = Some(text.to_string());
+    }
+    Ok(action)
+}
+
+// Rejects the attributes of an element which the conversion does not support, as ignoring them
+// would change the meaning of the document, such as `type="internal"` of a `<transition>`.
+// Attributes in other namespaces, such as the layout information of editors, are ignored.
+fn attributes(node: Node, supported: &[&str]) -> Result<(), String> {
+    match node
+        .attributes()
+        .find(|attribute| attribute.namespace().is_none() && !supported.contains(&attribute.name()))
+    {
+        Some(attribute) => Err(format!(
+            "The `{}` attribute of `<{}>` is not supported by smlang.",
+            attribute.name(),
+            node.tag_name().name()
+        )),
+        None => Ok(()),
+    }
+}
+
+// Reads an attribute which is used as an identifier in the generated code.
+fn identifier(node: Node, attribute: &str) -> Result<String, String> {
+    let value = node
+        .attribute(attribute)
+        .ok_or_else(|| format!("Missing `{}` attribute.", attribute))?;
+    syn::parse_str::<Ident>(value).map_err(|_| {
+        format!(
+            "`{}` is not supported as `{}`, it must be a valid Rust identifier.",
+            value, attribute
+        )
+    })?;
+    Ok(value.to_string())
+}
+
+fn unsupported(tag: &str) -> String {
+    format!("`<{}>` is not supported by smlang.", tag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn door() {
+        let converted = convert(
+            r#"<?xml version="1.0"?>
+<scxml xmlns="http://www.w3.org/2005/07/scxml" version="1.0" name="Door" initial="Closed">
+    <state id="Closed">
+        <transition event="Open" cond="unlocked" target="Opened">
+            <script>async open</script>
+        </transition>
+    </state>
+    <state id="Opened">
+        <onentry><script>light_on</script></onentry>
+        <onexit><script>light_off</script></onexit>
+        <transition event="Close Slam" target="Closed"/>
+        <transition event="Ping"/>
+        <transition event="Break" target="Broken"/>
+    </state>
+    <final id="Broken"/>
+</scxml>"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            converted.source,
+            "name: Door,
+states: {
+    Opened { entry / light_on, exit / light_off },
+    Broken { final },
+},
+transitions: {
+    *Closed + Open [unlocked] / async open = Opened,
+    Opened + Close = Closed,
+    Opened + Slam = Closed,
+    Opened + Ping = _,
+    Opened + Break = Broken,
+}
+"
+        );
+        assert_eq!(converted.xml_line(3), 8);
+        assert_eq!(converted.xml_line(7), 4);
+        assert_eq!(converted.xml_line(9), 11);
+    }
+
+    #[test]
+    fn locate_in_document() {
+        let converted = convert(
+            r#"<scxml name="Lamp" initial="Off">
+    <state id="Off">
+        <transition event="Toggle" target="On"/>
+    </state>
+    <state id="On">
+        <transition event="Toggle" target="Off"/>
+    </state>
+    <state id="Broken">
+        <transition event="Repair" target="Off"/>
+    </state>
+</scxml>"#,
+        )
+        .unwrap();
+
+        let (_, warnings) = crate::file::analyse(&converted.source, |location| {
+            format!("lamp.scxml:{}", converted.xml_line(location.line))
+        })
+        .unwrap();
+        assert_eq!(
+            warnings
+                .into_iter()
+                .map(|warning| warning.message)
+                .collect::<Vec<_>>(),
+            [
+                "lamp.scxml:9: State `Broken` is unreachable from the starting state `Off`",
+                "lamp.scxml:9: Event `Repair` can never be processed, as no reachable state has a transition for it",
+            ]
+        );
+    }
+
+    fn convert_error(document: &str) -> String {
+        let error = convert(document).err().unwrap();
+        format!("{}: {}", error.line, error.message)
+    }
+
+    #[test]
+    fn parallel_state() {
+        assert_eq!(
+            convert_error("<scxml>\n<parallel id=\"P\"/>\n</scxml>"),
+            "2: Parallel states are not supported by smlang."
+        );
+    }
+
+    #[test]
+    fn nested_state() {
+        assert_eq!(
+            convert_error("<scxml>\n<state id=\"A\">\n<state id=\"B\"/>\n</state>\n</scxml>"),
+            "3: Nested states are not supported by smlang."
+        );
+    }
+
+    #[test]
+    fn eventless_transition() {
+        assert_eq!(
+            convert_error(
+                "<scxml>\n<state id=\"A\">\n<transition target=\"A\"/>\n</state>\n</scxml>"
+            ),
+            "3: Eventless transitions are not supported by smlang, add an `event` attribute."
+        );
+    }
+
+    #[test]
+    fn unsupported_condition() {
+        assert_eq!(
+            convert_error(
+                "<scxml>\n<state id=\"A\">\n<transition event=\"E\" cond=\"x &gt; 1\" target=\"A\"/>\n</state>\n</scxml>"
+            ),
+            "3: The condition `x > 1` is not supported, only guard names combined with `!`, `&&` and `||` can be used."
+        );
+    }
+
+    #[test]
+    fn unsupported_executable_content() {
+        assert_eq!(
+            convert_error(
+                "<scxml>\n<state id=\"A\">\n<onentry><log expr=\"'hi'\"/></onentry>\n<transition event=\"E\" target=\"A\"/>\n</state>\n</scxml>"
+            ),
+            "3: `<log>` is not supported by smlang, actions are named with `<script>action</script>`."
+        );
+    }
+
+    #[test]
+    fn unsupported_attribute() {
+        assert_eq!(
+            convert_error(
+                "<scxml>\n<state id=\"A\">\n<transition event=\"E\" type=\"internal\" target=\"A\"/>\n</state>\n</scxml>"
+            ),
+            "3: The `type` attribute of `<transition>` is not supported by smlang."
+        );
+        assert_eq!(
+            convert_error(
+                "<scxml xmlns:qt=\"http://www.qt.io/2015/02/scxml-ext\">\n<state id=\"A\" qt:editorinfo=\"\">\n<transition event=\"E\" target=\"A\"/>\n</state>\n<state id=\"B\" initial=\"C\"/>\n</scxml>"
+            ),
+            "5: The `initial` attribute of `<state>` is not supported by smlang."
+        );
+    }
+
+    #[test]
+    fn initial_state_without_transitions() {
+        assert_eq!(
+            convert_error("<scxml>\n<final id=\"Done\"/>\n</scxml>"),
+            "2: The initial state `Done` has no outgoing transitions, which smlang requires of the starting state."
+        );
+    }
+
+    #[test]
+    fn export_round_trip() {
+        let sm = crate::parser::ParsedStateMachine::new(syn::parse_quote! {
+            name: Door,
+            states: {
+                Opened { entry / light_on, exit / async light_off },
+                Broken { final },
+            },
+            transitions: {
+                *Closed + Open(u32) [unlocked && !async jammed] / open = Opened,
+                Opened + Close = Closed,
+                Opened + Ping = _,
+                _ + Break = Broken,
+            }
+        })
+        .unwrap();
+        let converted = convert(&crate::export::generate_scxml(&sm)).unwrap();
+        let sm = crate::parser::ParsedStateMachine::new(syn::parse_str(&converted.source).unwrap())
+            .unwrap();
+        assert_eq!(sm.states.len(), 3);
+        assert_eq!(sm.starting_state, "Closed");
+        assert!(sm.state_declarations["Broken"].is_final);
+        assert_eq!(sm.states_events_mapping["Opened"].len(), 3);
+    }
+}
@@ -0,0 +1,540 @@
+use crate::parser::event::{EventMapping, Transition};
+use crate::parser::state_machine::{ActionFailure, LintLevel};
+use crate::parser::transition::visit_guards;
+use crate::parser::{locate, locate_message, AsyncIdent, Errors, Origin, ParsedStateMachine};
+use proc_macro2::{Span, TokenStream};
+use quote::{quote, ToTokens};
+use std::collections::{HashMap, HashSet};
+use syn::{parse, Ident};
+
+/// A problem in the state machine which does not prevent generating code.
+///
+/// Procedural macros cannot emit warnings on stable, so the warning is emitted by the compiler
+/// through the use of a deprecated constant at the span of the problem.
+pub struct Warning {
+    pub span: Span,
+    pub message: String,
+}
+
+impl ToTokens for Warning {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        let message = &self.message;
+        let warning = Ident::new("smlang_warning", self.span);
+        tokens.extend(quote! {
+            const _: () = {
+                #[deprecated(note = #message)]
+                #[allow(non_upper_case_globals)]
+                const smlang_warning: () = ();
+                #warning
+            };
+        });
+    }
+}
+
+/// A basic representation an action call signature.
+#[derive(PartialEq, Clone)]
+struct FunctionSignature {
+    // Function input arguments.
+    arguments: Vec<syn::Type>,
+
+    // Function result (if any).
+    result: Option<syn::Type>,
+
+    // Is the function async
+    is_async: bool,
+}
+
+impl FunctionSignature {
+    pub fn new(
+        input_data: Option<&syn::Type>,
+        event_data: Option<&syn::Type>,
+        output_data: Option<&syn::Type>,
+        is_async: bool,
+    ) -> Self {
+        let mut input_arguments = vec![];
+
+        if let Some(datatype) = input_data {
+            input_arguments.push(datatype.clone());
+        }
+
+        if let Some(datatype) = event_data {
+            input_arguments.push(datatype.clone());
+        }
+
+        let result = output_data.cloned();
+
+        Self {
+            arguments: input_arguments,
+            result,
+            is_async,
+        }
+    }
+
+    pub fn new_guard(
+        input_state: Option<&syn::Type>,
+        event: Option<&syn::Type>,
+        is_async: bool,
+    ) -> Self {
+        // Guards never have output data.
+        Self::new(input_state, event, None, is_async)
+    }
+}
+
+// Collect all transitions in the order they are defined in the DSL, so errors are reported for
+// the first offending line.
+fn transitions_in_order(sm: &ParsedStateMachine) -> Vec<(&String, &EventMapping, &Transition)> {
+    let mut transitions: Vec<_> = sm
+        .states_events_mapping
+        .iter()
+        .flat_map(|(in_state_name, event_mappings)| {
+            event_mappings.values().flat_map(move |event_mapping| {
+                event_mapping
+                    .transitions
+                    .iter()
+                    .map(move |transition| (in_state_name, event_mapping, transition))
+            })
+        })
+        .collect();
+    transitions.sort_by(|(a_state, _, a), (b_state, _, b)| {
+        a.index.cmp(&b.index).then_with(|| a_state.cmp(b_state))
+    });
+    transitions
+}
+
+// Create an error at the offending span, with a note at the conflicting earlier definition.
+fn conflict_error(
+    (span, origin): (Span, &Origin),
+    message: String,
+    (previous, previous_origin): (Span, &Origin),
+    note: String,
+) -> parse::Error {
+    let mut error = locate(parse::Error::new(span, message), origin);
+    error.combine(locate(parse::Error::new(previous, note), previous_origin));
+    error
+}
+
+// Verify action and guard function signatures.
+fn validate_action_signatures(sm: &ParsedStateMachine, errors: &mut Errors) {
+    // Collect all of the action call signatures.
+    let mut actions: HashMap<String, (FunctionSignature, &Ident, &Origin, bool)> = HashMap::new();
+
+    for (in_state_name, event_mapping, transition) in transitions_in_order(sm) {
+        let in_state_data = sm.state_data.data_types.get(in_state_name);
+        let out_state_data = sm
+            .state_data
+            .data_types
+            .get(&transition.out_state.to_string());
+
+        // Get the data associated with this event.
+        let event_data = sm
+            .event_data
+            .data_types
+            .get(&event_mapping.event.to_string());
+
+        if let Some(async_ident) = &transition.action {
+            let action = &async_ident.ident;
+            let signature = FunctionSignature::new(
+                in_state_data,
+                event_data,
+                out_state_data,
+                async_ident.is_async,
+            );
+            let fallible = async_ident.is_fallible(sm.fallible);
+
+            // If the action is not yet known, add it to our tracking list.
+            let (first_signature, first_use, first_origin, first_fallible) = actions
+                .entry(action.to_string())
+                .or_insert_with(|| (signature.clone(), action, &transition.origin, fallible));
+
+            // Check that the call signature is equivalent to the recorded signature for this
+            // action.
+            if first_signature != &signature {
+                errors.push(conflict_error(
+                    (action.span(), &transition.origin),
+                    format!("Action `{}` can only be reused when all input states, events, and output states have the same data", action),
+                    (first_use.span(), first_origin),
+                    format!("Action `{}` is first used here", action),
+                ));
+            }
+            if *first_fallible != fallible {
+                errors.push(conflict_error(
+                    (action.span(), &transition.origin),
+                    format!(
+                        "Action `{}` can only be reused when it is either always or never fallible",
+                        action
+                    ),
+                    (first_use.span(), first_origin),
+                    format!("Action `{}` is first used here", action),
+                ));
+            }
+        }
+    }
+}
+
+fn validate_guard_signatures(sm: &ParsedStateMachine, errors: &mut Errors) {
+    // Collect all of the guard call signatures.
+    let mut guards: HashMap<String, (FunctionSignature, Ident, &Origin, bool)> = HashMap::new();
+
+    for (in_state_name, event_mapping, transition) in transitions_in_order(sm) {
+        let in_state_data = sm.state_data.data_types.get(in_state_name);
+
+        // Get the data associated with this event.
+        let event_data = sm
+            .event_data
+            .data_types
+            .get(&event_mapping.event.to_string());
+
+        if let Some(guard_expression) = &transition.guard {
+            let result = visit_guards(guard_expression, |guard| {
+                let signature =
+                    FunctionSignature::new_guard(in_state_data, event_data, guard.is_async);
+                let fallible = guard.is_fallible(sm.fallible);
+
+                // If the guard is not yet known, add it to our tracking list.
+                let (first_signature, first_use, first_origin, first_fallible) =
+                    guards.entry(guard.ident.to_string()).or_insert_with(|| {
+                        (
+                            signature.clone(),
+                            guard.ident.clone(),
+                            &transition.origin,
+                            fallible,
+                        )
+                    });
+
+                // Check that the call signature is equivalent to the recorded signature for this
+                // guard.
+                if first_signature != &signature {
+                    errors.push(conflict_error(
+                        (guard.ident.span(), &transition.origin),
+                        format!("Guard `{}` can only be reused when all input states and events have the same data", guard.ident),
+                        (first_use.span(), first_origin),
+                        format!("Guard `{}` is first used here", guard.ident),
+                    ));
+                }
+                if *first_fallible != fallible {
+                    errors.push(conflict_error(
+                        (guard.ident.span(), &transition.origin),
+                        format!("Guard `{}` can only be reused when it is either always or never fallible", guard.ident),
+                        (first_use.span(), first_origin),
+                        format!("Guard `{}` is first used here", guard.ident),
+                    ));
+                }
+                Ok(())
+            });
+            errors.check_at(&transition.origin, result);
+        }
+    }
+}
+
+// Verify the entry and exit actions and the activities declared for states.
+fn validate_state_actions(sm: &ParsedStateMachine, errors: &mut Errors) {
+    let mut transition_actions = HashMap::new();
+    for event_mappings in sm.states_events_mapping.values() {
+        for event_mapping in event_mappings.values() {
+            for transition in &event_mapping.transitions {
+                if let Some(action) = &transition.action {
+                    transition_actions.insert(action.ident.to_string(), action.ident.clone());
+                }
+            }
+        }
+    }
+
+    let mut state_actions: HashMap<String, (&AsyncIdent, &Origin)> = HashMap::new();
+    let mut activities: HashMap<String, (&AsyncIdent, &Origin)> = HashMap::new();
+    let mut declarations: Vec<_> = sm.state_declarations.values().collect();
+    declarations.sort_by_key(|declaration| declaration.ident.to_string());
+
+    for declaration in declarations {
+        for action in [&declaration.entry, &declaration.exit]
+            .iter()
+            .filter_map(|action| action.as_ref())
+        {
+            let name = action.ident.to_string();
+            if transition_actions.contains_key(&name) {
+                errors.push_at(&declaration.origin, parse::Error::new(
+                    action.ident.span(),
+                    format!("Action `{}` is used both as a transition action and as an entry or exit action, which have different signatures", name),
+                ));
+            }
+
+            // The same entry or exit action may be reused by multiple states.
+            let (previous, previous_origin) = state_actions
+                .entry(name)
+                .or_insert((action, &declaration.origin));
+            if previous.is_async != action.is_async {
+                errors.push(conflict_error(
+                    (action.ident.span(), &declaration.origin),
+                    format!(
+                        "Action `{}` can only be reused when it is either always or never async",
+                        action.ident
+                    ),
+                    (previous.ident.span(), previous_origin),
+                    format!("Action `{}` is first used here", action.ident),
+                ));
+            }
+        }
+
+        if let Some(activity) = &declaration.activity {
+            let name = activity.ident.to_string();
+            if transition_actions.contains_key(&name) || state_actions.contains_key(&name) {
+                errors.push_at(
+                    &declaration.origin,
+                    parse::Error::new(
+                        activity.ident.span(),
+                        format!(
+                        "Activity `{}` is also used as an action, which has a different signature",
+                        name
+                    ),
+                    ),
+                );
+            }
+            activities.insert(name, (activity, &declaration.origin));
+        }
+    }
+
+    // Entry and exit actions of states declared later may still clash with activities
+    for (name, (activity, origin)) in activities {
+        if state_actions.contains_key(&name) {
+            errors.push_at(
+                origin,
+                parse::Error::new(
+                    activity.ident.span(),
+                    format!(
+                        "Activity `{}` is also used as an action, which has a different signature",
+                        name
+                    ),
+                ),
+            );
+        }
+    }
+}
+
+// Whether the guard expression and the action of a transition can fail.
+fn fallibility(sm: &ParsedStateMachine, transition: &Transition) -> (bool, bool) {
+    let mut fallible_guard = false;
+    if let Some(guard_expression) = &transition.guard {
+        let _ = visit_guards(guard_expression, |guard| {
+            fallible_guard |= guard.is_fallible(sm.fallible);
+            Ok(())
+        });
+    }
+    let fallible_action = transition
+        .action
+        .as_ref()
+        .is_some_and(|action| action.is_fallible(sm.fallible));
+    (fallible_guard, fallible_action)
+}
+
+// Verify that the error states of transitions can be entered, which requires a fallible guard or
+// action.
+fn validate_error_states(sm: &ParsedStateMachine, errors: &mut Errors) {
+    let mut reported = HashSet::new();
+
+    for (_, _, transition) in transitions_in_order(sm) {
+        let error_state = match &transition.error_state {
+            Some(error_state) => error_state,
+            None => continue,
+        };
+
+        let (fallible_guard, fallible_action) = fallibility(sm, transition);
+
+        // Transitions expanded from a wildcard are reported once
+        if !fallible_guard && !fallible_action && reported.insert(transition.index) {
+            errors.push_at(
+                &transition.origin,
+                parse::Error::new(
+                    error_state.span(),
+                    format!(
+                        "Error state `{}` has no effect, as neither the guard nor the action of the transition can fail.",
+                        error_state
+                    ),
+                ),
+            );
+        }
+    }
+}
+
+// Collect the states which can be reached from the starting state.
+fn reachable_states(sm: &ParsedStateMachine) -> HashSet<String> {
+    let mut reachable = HashSet::new();
+    let mut stack = vec![sm.starting_state.to_string()];
+    while let Some(state) = stack.pop() {
+        if !reachable.insert(state.clone()) {
+            continue;
+        }
+
+        // The history state only returns to states which were reachable before
+        if let Some(event_mappings) = sm.states_events_mapping.get(&state) {
+            for event_mapping in event_mappings.values() {
+                for transition in &event_mapping.transitions {
+                    if sm.states.contains_key(&transition.out_state.to_string()) {
+                        stack.push(transition.out_state.to_string());
+                    }
+
+                    // The states entered when the guard or action fails
+                    let (fallible_guard, fallible_action) = fallibility(sm, transition);
+                    if let Some(state) = transition.error_state.as_ref().or(sm.on_error.as_ref()) {
+                        if fallible_guard || fallible_action {
+                            stack.push(state.to_string());
+                        }
+                    } else if let ActionFailure::Enter(state) = &sm.action_failure {
+                        if fallible_action {
+                            stack.push(state.to_string());
+                        }
+                    }
+                }
+            }
+        }
+    }
+    reachable
+}
+
+// Analyse the state machine graph for states which can never be entered, states without a way
+// out and events which can never be processed.
+fn validate_reachability(sm: &ParsedStateMachine, warnings: &mut Vec<Warning>) {
+    let reachable = reachable_states(sm);
+
+    // Without any final states, states without outgoing transitions are intended to be final
+    let has_final_states = sm
+        .state_declarations
+        .values()
+        .any(|declaration| declaration.is_final);
+
+    let mut states: Vec<_> = sm.states.iter().collect();
+    states.sort_by_key(|(name, _)| name.to_string());
+
+    for (name, state) in states {
+        let declaration = sm.state_declarations.get(name);
+        let is_final = declaration.is_some_and(|declaration| declaration.is_final);
+
+        if !reachable.contains(name) {
+            let (span, origin) = match declaration {
+                Some(declaration) => (declaration.ident.span(), &declaration.origin),
+                None => (state.span(), &sm.state_origins[name]),
+            };
+            warnings.push(Warning {
+                span,
+                message: locate_message(
+                    format!(
+                        "{} `{}` is unreachable from the starting state `{}`",
+                        if is_final { "Final state" } else { "State" },
+                        name,
+                        sm.starting_state
+                    ),
+                    origin,
+                ),
+            });
+        } else if has_final_states && !is_final && sm.states_events_mapping[name].is_empty() {
+            warnings.push(Warning {
+                span: state.span(),
+                message: locate_message(
+                    format!(
+                        "State `{}` has no outgoing transitions, but is not declared as final",
+                        name
+                    ),
+                    &sm.state_origins[name],
+                ),
+            });
+        }
+    }
+
+    // An event can only be processed if a reachable state has a transition for it
+    let mut events: Vec<_> = sm.events.iter().collect();
+    events.sort_by_key(|(name, _)| name.to_string());
+
+    for (name, event) in events {
+        let processable = reachable.iter().any(|state| {
+            sm.states_events_mapping
+                .get(state)
+                .is_some_and(|event_mappings| event_mappings.contains_key(name))
+        });
+
+        if !processable {
+            warnings.push(Warning {
+                span: event.span(),
+                message: locate_message(
+                    format!(
+                        "Event `{}` can never be processed, as no reachable state has a transition for it",
+                        name
+                    ),
+                    &sm.event_origins[name],
+                ),
+            });
+        }
+    }
+}
+
+fn validate_unreachable_transitions(sm: &ParsedStateMachine, errors: &mut Errors) {
+    // Check the state and event combinations in the order they are defined
+    let mut event_mappings: Vec<_> = sm
+        .states_events_mapping
+        .iter()
+        .flat_map(|(in_state, event_mappings)| {
+            event_mappings
+                .values()
+                .map(move |event_mapping| (in_state, event_mapping))
+        })
+        .collect();
+    event_mappings.sort_by_key(|(in_state, event_mapping)| {
+        (event_mapping.transitions[0].index, in_state.to_string())
+    });
+
+    for (in_state, event_mapping) in event_mappings {
+        let event = &event_mapping.event;
+        let mut unguarded: Option<&Transition> = None;
+        for t in &event_mapping.transitions {
+            match (&t.guard, unguarded) {
+                (Some(g), Some(previous)) => {
+                    // Guarded transition AFTER an unguarded one
+                    errors.push(conflict_error(
+                        (g.span(), &t.origin),
+                        format!("{} + {}: [{}] : guarded transition is unreachable because it follows an unguarded transition, which handles all cases",
+                                in_state, event, crate::export::guard_string(g)),
+                        (previous.in_state_span, &previous.origin),
+                        format!("{} + {}: the unguarded transition is defined here", in_state, event),
+                    ));
+                    break;
+                }
+                (None, Some(previous)) => {
+                    errors.push(conflict_error(
+                        (t.in_state_span, &t.origin),
+                        format!("{} + {}: State and event combination specified multiple times, remove duplicates.", in_state, event),
+                        (previous.in_state_span, &previous.origin),
+                        format!("{} + {}: first defined here", in_state, event),
+                    ));
+                    break;
+                }
+                (None, None) => unguarded = Some(t),
+                (Some(_), None) => (),
+            }
+        }
+    }
+}
+
+/// Validate coherency of the state machine.
+pub fn validate(sm: &ParsedStateMachine) -> Result<Vec<Warning>, parse::Error> {
+    // All passes are run, so every problem is reported at once
+    let mut errors = Errors::default();
+    validate_action_signatures(sm, &mut errors);
+    validate_guard_signatures(sm, &mut errors);
+    validate_state_actions(sm, &mut errors);
+    validate_unreachable_transitions(sm, &mut errors);
+    validate_error_states(sm, &mut errors);
+
+    let mut warnings = Vec::new();
+    validate_reachability(sm, &mut warnings);
+
+    let warnings = match sm.lint_level {
+        LintLevel::Allow => Vec::new(),
+        LintLevel::Warn => warnings,
+        LintLevel::Deny => {
+            for warning in warnings {
+                errors.push(parse::Error::new(warning.span, warning.message));
+            }
+            Vec::new()
+        }
+    };
+
+    errors.finish()?;
+    Ok(warnings)
+}
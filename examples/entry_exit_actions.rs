@@ -0,0 +1,52 @@
+//! An example of declaring entry and exit actions for states
+
+#![deny(missing_docs)]
+
+use smlang::statemachine;
+
+statemachine! {
+    name: Link,
+    states: {
+        Connected { entry / start_timer, exit / stop_timer },
+        Reconnecting { entry / start_timer },
+    },
+    transitions: {
+        *Idle + Connect = Connected,
+        Connected + Lost = Reconnecting,
+        Reconnecting + Connect = Connected,
+        Reconnecting + Timeout = Idle,
+    },
+}
+
+/// Context
+pub struct Context {
+    running_timers: i32,
+}
+
+impl LinkStateMachineContext for Context {
+    fn start_timer(&mut self) {
+        self.running_timers += 1;
+    }
+
+    fn stop_timer(&mut self) {
+        self.running_timers -= 1;
+    }
+}
+
+fn main() {
+    let mut sm = LinkStateMachine::new(Context { running_timers: 0 });
+
+    let _ = sm.process_event(LinkEvents::Connect).unwrap();
+    assert!(matches!(sm.state(), &LinkStates::Connected));
+    assert_eq!(sm.context().running_timers, 1);
+
+    // The timer of `Connected` is stopped and the one of `Reconnecting` started
+    let _ = sm.process_event(LinkEvents::Lost).unwrap();
+    assert!(matches!(sm.state(), &LinkStates::Reconnecting));
+    assert_eq!(sm.context().running_timers, 1);
+
+    // `Reconnecting` has no exit action, so its timer keeps running
+    let _ = sm.process_event(LinkEvents::Timeout).unwrap();
+    assert!(matches!(sm.state(), &LinkStates::Idle));
+    assert_eq!(sm.context().running_timers, 1);
+}
@@ -60,5 +60,11 @@ fn main() {
 
     let r = sm.process_event(Events::Event1(MyEventData(1)));
 
-    assert!(matches!(r, Err(Error::GuardFailed(GuardError::Custom))));
+    assert!(matches!(
+        r,
+        Err(Error::GuardFailed {
+            guard: "guard1",
+            error: GuardError::Custom
+        })
+    ));
 }
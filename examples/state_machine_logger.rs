@@ -66,6 +66,10 @@ impl StateMachineContext for Context {
         }
     }
 
+    fn log_guard_result(&self, guard: &'static str, result: &Result<bool, ()>) {
+        println!("[StateMachineLogger]\t`{}` returned {:?}", guard, result);
+    }
+
     fn log_action(&self, action: &'static str) {
         println!("[StateMachineLogger]\tRunning `{}`", action);
     }
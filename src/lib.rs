@@ -81,4 +81,32 @@
 //! ```
 #![no_std]
 
-pub use smlang_macros::statemachine;
+#[cfg(feature = "scxml")]
+pub use smlang_macros::statemachine_scxml;
+pub use smlang_macros::{statemachine, statemachine_file};
+
+/// The stream of events consumed by the generated `run()` of state machines with do-activities.
+pub use futures_core::Stream;
+
+/// Polls a future once with a waker which does nothing, returning its output if it is ready.
+///
+/// Used by the generated `try_process_event_sync()` of async state machines, which only calls it
+/// when processing the event awaits nothing, so the future is ready on the first poll.
+#[doc(hidden)]
+pub fn poll_once<F: core::future::Future>(future: F) -> Option<F::Output> {
+    use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+    fn clone(_: *const ()) -> RawWaker {
+        RawWaker::new(core::ptr::null(), &VTABLE)
+    }
+    fn noop(_: *const ()) {}
+    static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+
+    // SAFETY: the functions of the vtable do nothing, so they uphold its contract for any pointer
+    let waker = unsafe { Waker::from_raw(RawWaker::new(core::ptr::null(), &VTABLE)) };
+    let future = core::pin::pin!(future);
+    match future.poll(&mut Context::from_waker(&waker)) {
+        Poll::Ready(output) => Some(output),
+        Poll::Pending => None,
+    }
+}
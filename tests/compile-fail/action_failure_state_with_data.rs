@@ -0,0 +1,13 @@
+use smlang::statemachine;
+
+statemachine! {
+    // The state entered when an action fails is constructed without data
+    action_failure: enter(Fault),
+    transitions: {
+        *Idle + Start / start = Running,
+        Running + Fail / fail = Fault(u32),
+        Fault(u32) + Reset = Idle,
+    }
+}
+
+fn main() {}
@@ -0,0 +1,15 @@
+use smlang::statemachine;
+
+statemachine! {
+    states: {
+        State1 { entry / async connect },
+        // `connect` is async for `State1`, so it cannot be sync here
+        State2 { entry / connect },
+    },
+    transitions: {
+        *State1 + Event = State2,
+        State2 + Event = State1,
+    }
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+use smlang::statemachine;
+
+statemachine! {
+    transitions: {
+        // Neither the guard nor the action of these transitions can fail
+        *Idle + Start [ready!] / start! = Running ! Stopped,
+        Running + Stop = Stopped ! Idle,
+        Stopped + Start = Idle,
+    }
+}
+
+fn main() {}
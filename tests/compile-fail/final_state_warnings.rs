@@ -0,0 +1,21 @@
+#![deny(deprecated)]
+
+use smlang::statemachine;
+
+statemachine! {
+    states: {
+        Done { final },
+        Aborted { final },
+        Lost { final },
+    },
+    transitions: {
+        *Init + Start = Running,
+        Running + Finish = Done,
+        Running + Stop = Stopped,
+        Stopped + Abort = Aborted,
+        Unknown + Abort = Lost,
+        Stopped + Reset = Idle,
+    }
+}
+
+fn main() {}
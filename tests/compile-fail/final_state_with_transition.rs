@@ -0,0 +1,13 @@
+use smlang::statemachine;
+
+statemachine! {
+    states: {
+        Done { final },
+    },
+    transitions: {
+        *Init + Finish = Done,
+        Done + Restart = Init, //~ Final states cannot have outgoing transitions.
+    }
+}
+
+fn main() {}
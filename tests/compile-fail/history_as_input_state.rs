@@ -0,0 +1,10 @@
+use smlang::statemachine;
+
+statemachine! {
+    transitions: {
+        *State1 + Event1 = State2,
+        H + Event2 = State1, //~ `H` is reserved for the history state
+    }
+}
+
+fn main() {}
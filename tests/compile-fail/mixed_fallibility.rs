@@ -0,0 +1,14 @@
+use smlang::statemachine;
+
+statemachine! {
+    fallible: false,
+    transitions: {
+        *Init + Event [guard] / action = State1,
+
+        // These transitions are not valid because `guard` and `action` are fallible here, but
+        // not earlier.
+        State1 + Event [guard?] / action? = State2,
+    }
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+use smlang::statemachine;
+
+statemachine! {
+    transitions: {
+        *State1 + Event1 = State2,
+        State2 + + Event2 = State3, //~ expected identifier
+        State3 + Event3 [guard && ] = State1, //~ unexpected end of input
+        State3 + Event4 / = State1, //~ expected identifier
+    }
+}
+
+fn main() {}
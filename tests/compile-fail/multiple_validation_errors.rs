@@ -0,0 +1,14 @@
+use smlang::statemachine;
+
+statemachine! {
+    transitions: {
+        *Init + Event / action = State1(u32),
+        State1(u32) + Event = Init,
+        State1(u32) + Event [guard] = State2, //~ guarded transition is unreachable
+        State2 + Start = Init,
+        State2 + Start / action = State1(u32), //~ State and event combination specified multiple times
+        Init + Stop [guard] / action = State1(u32), //~ Guard `guard` can only be reused
+    }
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use smlang::statemachine;
+
+statemachine! {
+    transitions: {
+        // No starting state, and `State2` has different data in both transitions
+        State1 + Event1 = State2(u32),
+        State2(bool) + Event2 = State1,
+    }
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+use smlang::statemachine;
+
+statemachine! {
+    states: {
+        State2 { entry / action },
+    },
+    transitions: {
+        *State1 + Event1 / action = State2,
+    }
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+use smlang::statemachine;
+
+statemachine! {
+    states: {
+        State3 { entry / enter_state3 }, //~ State `State3` is declared but not used in any transition.
+    },
+    transitions: {
+        *State1 + Event1 = State2,
+    }
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use smlang::statemachine;
+
+statemachine! {
+    transitions: {
+        // `Fault` must be used in the transitions to be entered when the action fails
+        *Idle + Start / start = Running ! Fault,
+        Running + Stop = Idle,
+    }
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+use smlang::statemachine;
+
+statemachine! {
+    lints: deny,
+    transitions: {
+        *Init + Start = Running,
+        Running + Stop = Init,
+        Orphan + Adopt = Running,
+    }
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+extern crate smlang;
+
+use smlang::statemachine;
+
+statemachine! {
+    generics: <'a, T: Copy = u8>,
+    transitions: {
+        *State1 + Event1(&'a T) = State2,
+    }
+}
+
+fn main() {}
@@ -2,6 +2,7 @@ extern crate smlang;
 
 use derive_more::Display;
 
+use core::future::Future;
 use smlang::statemachine;
 
 mod internal_macros {
@@ -71,6 +72,8 @@ fn multiple_lifetimes() {
     pub struct Z;
 
     statemachine! {
+        // State4 is only used to test the lifetimes of its transition
+        lints: allow,
         transitions: {
             *State1 + Event1(&'a X) [guard1] / action1 = State2(&'a X),
             State2(&'a X) + Event2(&'b Y) [guard2] / action2 = State3((&'a X, &'b Y)),
@@ -234,6 +237,211 @@ fn async_on_entry_and_exit() {
     });
 }
 
+#[test]
+fn async_send_futures() {
+    use smol;
+
+    statemachine! {
+        async_send: true,
+        entry_exit_async: true,
+        transitions: {
+            *State1 + Event1 [async guard1] / async action1 = State2(u32),
+            State2(u32) + Event2 / action2 = State1,
+        }
+    }
+
+    struct Context {
+        exits: u32,
+    }
+
+    impl StateMachineContext for Context {
+        async fn guard1(&self) -> Result<bool, ()> {
+            Ok(true)
+        }
+
+        async fn action1(&mut self) -> Result<u32, ()> {
+            Ok(self.exits)
+        }
+
+        fn action2(&mut self, _state_data: &u32) -> Result<(), ()> {
+            Ok(())
+        }
+
+        async fn on_exit_state1(&mut self) {
+            self.exits += 1;
+        }
+    }
+
+    fn assert_send<F: Send>(future: F) -> F {
+        future
+    }
+
+    // The bounds of the context trait are enough for the future to be `Send`
+    async fn process<T: StateMachineContext + Send>(
+        sm: &mut StateMachine<T>,
+        event: Events,
+    ) -> Result<(), Error> {
+        assert_send(sm.process_event(event)).await?;
+        Ok(())
+    }
+
+    let mut sm = StateMachine::new(Context { exits: 0 });
+
+    smol::block_on(process(&mut sm, Events::Event1)).unwrap();
+    assert!(matches!(sm.state(), &States::State2(1)));
+
+    smol::block_on(assert_send(process(&mut sm, Events::Event2))).unwrap();
+    assert!(matches!(sm.state(), &States::State1));
+}
+
+#[test]
+fn try_process_event_sync() {
+    use smol;
+
+    statemachine! {
+        states: {
+            State3 { entry / async connect },
+        },
+        transitions: {
+            *State1 + Event1(u32) [guard1] / action1 = State2(u32),
+            State2(u32) + Event1(u32) [async guard2] = State1,
+            State2(u32) + Event2 = State3,
+            State3 + Event2 / action2 = State3,
+        }
+    }
+
+    #[derive(Default)]
+    struct Context {
+        connected: bool,
+    }
+
+    impl StateMachineContext for Context {
+        fn guard1(&self, event_data: &u32) -> Result<bool, ()> {
+            Ok(*event_data > 0)
+        }
+
+        fn action1(&mut self, event_data: u32) -> Result<u32, ()> {
+            Ok(event_data)
+        }
+
+        async fn guard2(&self, _state_data: &u32, _event_data: &u32) -> Result<bool, ()> {
+            Ok(true)
+        }
+
+        fn action2(&mut self) -> Result<(), ()> {
+            Ok(())
+        }
+
+        async fn connect(&mut self) {
+            self.connected = true;
+        }
+    }
+
+    let mut sm = StateMachine::new(Context::default());
+
+    // Synchronous guards and actions
+    assert!(matches!(
+        sm.try_process_event_sync(Events::Event1(0)),
+        Err(Error::TransitionsFailed)
+    ));
+    assert!(matches!(
+        sm.try_process_event_sync(Events::Event1(3)),
+        Ok(&States::State2(3))
+    ));
+
+    // An async guard
+    assert!(matches!(
+        sm.try_process_event_sync(Events::Event1(1)),
+        Err(Error::RequiresAsync)
+    ));
+    assert!(matches!(sm.state(), &States::State2(3)));
+
+    // An async entry action, processed asynchronously as shown in the README
+    if let Err(Error::RequiresAsync) = sm.try_process_event_sync(Events::Event2) {
+        assert!(!sm.context().connected);
+        smol::block_on(sm.process_event(Events::Event2)).unwrap();
+    } else {
+        panic!("the async entry action requires async processing");
+    }
+    assert!(sm.context().connected);
+
+    // Internal transitions do not enter the state again
+    assert!(matches!(
+        sm.try_process_event_sync(Events::Event2),
+        Ok(&States::State3)
+    ));
+
+    assert!(matches!(
+        sm.try_process_event_sync(Events::Event1(1)),
+        Err(Error::InvalidEvent)
+    ));
+}
+
+#[test]
+fn interrupted_transitions() {
+    use smol::future;
+
+    statemachine! {
+        states_attr: #[derive(Debug)],
+        states: {
+            State1 { exit / async disconnect },
+        },
+        transitions: {
+            *State1 + Event1 = State2,
+            State2 + Event2 [async ready] = State1,
+        }
+    }
+
+    #[derive(Default)]
+    struct Context {
+        disconnects: u32,
+    }
+
+    impl StateMachineContext for Context {
+        async fn ready(&self) -> Result<bool, ()> {
+            future::yield_now().await;
+            Ok(true)
+        }
+
+        async fn disconnect(&mut self) {
+            future::yield_now().await;
+            self.disconnects += 1;
+        }
+    }
+
+    let mut sm = StateMachine::new(Context::default());
+    assert!(!sm.is_in_transition());
+
+    // Dropping the future while the exit action awaits
+    assert!(future::block_on(future::poll_once(sm.process_event(Events::Event1))).is_none());
+    assert!(sm.is_in_transition());
+    assert_eq!(sm.state(), &States::State1);
+    assert!(matches!(
+        future::block_on(sm.process_event(Events::Event1)),
+        Err(Error::InTransition)
+    ));
+
+    sm.recover();
+    assert!(!sm.is_in_transition());
+    future::block_on(sm.process_event(Events::Event1)).unwrap();
+    assert_eq!(sm.state(), &States::State2);
+    assert_eq!(sm.context().disconnects, 1);
+
+    // Dropping the future while a guard awaits, before the transition started
+    assert!(future::block_on(future::poll_once(sm.process_event(Events::Event2))).is_none());
+    assert!(!sm.is_in_transition());
+    assert_eq!(sm.state(), &States::State2);
+    future::block_on(sm.process_event(Events::Event2)).unwrap();
+
+    assert!(future::block_on(future::poll_once(sm.process_event(Events::Event1))).is_none());
+    sm.recover_with_state(States::State2);
+    assert!(!sm.is_in_transition());
+    assert!(matches!(
+        future::block_on(sm.process_event(Events::Event2)),
+        Ok(&States::State1)
+    ));
+}
+
 #[test]
 fn guard_expressions() {
     #[derive(PartialEq, Display)]
@@ -552,3 +760,1033 @@ fn test_specify_attrs() {
     assert_transition!(sm, Events::tostate2, States::State2, 0);
     assert_transition!(sm, Events::tostate3, States::State3, 1);
 }
+
+#[test]
+fn declared_entry_and_exit_actions() {
+    statemachine! {
+        states: {
+            Connected { entry / start_timer, exit / stop_timer },
+            Reconnecting { entry / start_timer },
+        },
+        transitions: {
+            *Idle + Connect = Connected,
+            Connected + Ping = Connected,
+            Connected + Lost = Reconnecting,
+            Reconnecting + Connect = Connected,
+        },
+        states_attr: #[derive(Debug, Clone, Copy)]
+    }
+
+    #[derive(Debug, Default)]
+    pub struct Context {
+        timers_started: u32,
+        timers_stopped: u32,
+    }
+
+    impl StateMachineContext for Context {
+        fn start_timer(&mut self) {
+            self.timers_started += 1;
+        }
+
+        fn stop_timer(&mut self) {
+            self.timers_stopped += 1;
+        }
+    }
+
+    let mut sm = StateMachine::new(Context::default());
+
+    sm.process_event(Events::Connect).unwrap();
+    assert_eq!(sm.context().timers_started, 1);
+    assert_eq!(sm.context().timers_stopped, 0);
+
+    // Internal transitions do not run the entry and exit actions
+    sm.process_event(Events::Ping).unwrap();
+    assert_eq!(sm.context().timers_started, 1);
+    assert_eq!(sm.context().timers_stopped, 0);
+
+    sm.process_event(Events::Lost).unwrap();
+    assert!(matches!(sm.state(), &States::Reconnecting));
+    assert_eq!(sm.context().timers_started, 2);
+    assert_eq!(sm.context().timers_stopped, 1);
+
+    sm.process_event(Events::Connect).unwrap();
+    assert_eq!(sm.context().timers_started, 3);
+    assert_eq!(sm.context().timers_stopped, 1);
+}
+
+#[test]
+fn async_declared_entry_action() {
+    use smol;
+
+    smol::block_on(async {
+        statemachine! {
+            states: {
+                State2 { entry / async connect },
+            },
+            transitions: {
+                *State1 + Event1 = State2,
+            }
+        }
+
+        struct Context {
+            connected: bool,
+        }
+
+        impl StateMachineContext for Context {
+            async fn connect(&mut self) {
+                self.connected = true;
+            }
+        }
+
+        let mut sm = StateMachine::new(Context { connected: false });
+
+        sm.process_event(Events::Event1).await.unwrap();
+        assert!(matches!(sm.state(), &States::State2));
+        assert!(sm.context().connected);
+    });
+}
+
+#[test]
+fn do_activities() {
+    use smol;
+
+    smol::block_on(async {
+        statemachine! {
+            states: {
+                Connected { do / async poll_socket },
+            },
+            transitions: {
+                *Idle + Connect = Connected,
+                Connected + Data / store = Connected,
+                Connected + Disconnect = Idle,
+            }
+        }
+
+        struct Context {
+            packets: u32,
+            sender: Option<smol::channel::Sender<Events>>,
+        }
+
+        impl StateMachineContext for Context {
+            fn poll_socket(&mut self) -> impl Future<Output = Option<Events>> + 'static {
+                let packets = self.packets;
+                async move {
+                    if packets < 3 {
+                        Some(Events::Data)
+                    } else {
+                        None
+                    }
+                }
+            }
+
+            fn store(&mut self) -> Result<(), ()> {
+                self.packets += 1;
+                if self.packets == 3 {
+                    // Disconnect and close the event stream
+                    let sender = self.sender.take().unwrap();
+                    sender.try_send(Events::Disconnect).unwrap();
+                }
+                Ok(())
+            }
+        }
+
+        let (sender, receiver) = smol::channel::unbounded();
+        sender.try_send(Events::Connect).unwrap();
+
+        let mut sm = StateMachine::new(Context {
+            packets: 0,
+            sender: Some(sender),
+        });
+
+        // The activity of `Connected` produces events while the stream has none
+        sm.run(receiver).await.unwrap();
+        assert!(matches!(sm.state(), &States::Idle));
+        assert_eq!(sm.context().packets, 3);
+    });
+}
+
+#[test]
+fn do_activity_across_internal_transitions() {
+    use smol::channel::{Receiver, Sender};
+
+    statemachine! {
+        states: {
+            Connected { do / async receive },
+        },
+        transitions: {
+            *Idle + Connect = Connected,
+            Connected + Ping / ping = Connected,
+            Connected + Data(u32) / store = Connected,
+            Connected + Disconnect = Idle,
+        }
+    }
+
+    struct Context {
+        started: u32,
+        received: Vec<u32>,
+        events: Option<Sender<Events>>,
+        socket: (Sender<u32>, Receiver<u32>),
+    }
+
+    impl StateMachineContext for Context {
+        fn receive(&mut self) -> impl Future<Output = Option<Events>> + 'static {
+            self.started += 1;
+            // The first activity pings while it waits for data
+            let events = self.events.clone().filter(|_| self.started == 1);
+            let socket = self.socket.1.clone();
+            async move {
+                if let Some(events) = events {
+                    events.send(Events::Ping).await.unwrap();
+                }
+                socket.recv().await.ok().map(Events::Data)
+            }
+        }
+
+        fn ping(&mut self) -> Result<(), ()> {
+            self.socket.0.try_send(7).unwrap();
+            Ok(())
+        }
+
+        fn store(&mut self, data: u32) -> Result<(), ()> {
+            self.received.push(data);
+            // Disconnect and close the event stream
+            let events = self.events.take().unwrap();
+            events.try_send(Events::Disconnect).unwrap();
+            Ok(())
+        }
+    }
+
+    let (sender, receiver) = smol::channel::unbounded();
+    sender.try_send(Events::Connect).unwrap();
+
+    let mut sm = StateMachine::new(Context {
+        started: 0,
+        received: Vec::new(),
+        events: Some(sender),
+        socket: smol::channel::unbounded(),
+    });
+
+    // The activity still receives the data sent by the internal transition, and is only started
+    // again after it returned the data
+    smol::block_on(sm.run(receiver)).unwrap();
+    assert!(matches!(sm.state(), &States::Idle));
+    assert_eq!(sm.context().received, [7]);
+    assert_eq!(sm.context().started, 2);
+}
+
+#[test]
+fn history_state() {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct Speed(u32);
+
+    statemachine! {
+        transitions: {
+            *Idle + Start = Manual,
+            Manual + Auto / enter_auto = Automatic(Speed),
+            Automatic(Speed) + Manual = Manual,
+            Manual | Automatic(Speed) + Pause = Paused,
+            Paused + Resume = H,
+        },
+        states_attr: #[derive(Debug, Clone, Copy)]
+    }
+
+    #[derive(Default)]
+    pub struct Context {
+        entered_manual: u32,
+    }
+
+    impl StateMachineContext for Context {
+        fn enter_auto(&mut self) -> Result<Speed, ()> {
+            Ok(Speed(42))
+        }
+
+        fn on_entry_manual(&mut self) {
+            self.entered_manual += 1;
+        }
+    }
+
+    let mut sm = StateMachine::new(Context::default());
+
+    // Every exited state is stored as history
+    sm.process_event(Events::Start).unwrap();
+    assert!(matches!(sm.history(), Some(&States::Idle)));
+
+    sm.process_event(Events::Pause).unwrap();
+    sm.process_event(Events::Resume).unwrap();
+    assert!(matches!(sm.state(), &States::Manual));
+    assert!(matches!(sm.history(), Some(&States::Paused)));
+    assert_eq!(sm.context().entered_manual, 2);
+
+    // The data of the state is restored as well
+    sm.process_event(Events::Auto).unwrap();
+    sm.process_event(Events::Pause).unwrap();
+    sm.process_event(Events::Resume).unwrap();
+    assert!(matches!(sm.state(), &States::Automatic(Speed(42))));
+
+    sm.process_event(Events::Manual).unwrap();
+    sm.process_event(Events::Auto).unwrap();
+
+    // The history is preserved when restoring a snapshot
+    let state = *sm.state();
+    let history = sm.history().copied();
+    let mut sm = StateMachine::new_with_history(Context::default(), state, history);
+    sm.process_event(Events::Pause).unwrap();
+    sm.process_event(Events::Resume).unwrap();
+    assert!(matches!(sm.state(), &States::Automatic(Speed(42))));
+
+    let mut sm = StateMachine::new_with_state(Context::default(), States::Paused);
+    assert_eq!(sm.process_event(Events::Resume), Err(Error::NoHistory));
+    assert!(matches!(sm.state(), &States::Paused));
+}
+
+#[test]
+fn final_states() {
+    statemachine! {
+        states: {
+            Done { final },
+            Aborted { final },
+        },
+        transitions: {
+            *Init + Start = Running,
+            Running + Finish = Done,
+            _ + Abort = Aborted,
+        },
+        states_attr: #[derive(Debug)]
+    }
+
+    struct Context;
+    impl StateMachineContext for Context {}
+
+    let mut sm = StateMachine::new(Context);
+    assert!(!sm.is_finished());
+
+    sm.process_event(Events::Start).unwrap();
+    sm.process_event(Events::Finish).unwrap();
+    assert!(matches!(sm.state(), &States::Done));
+    assert!(sm.is_finished());
+
+    // The wildcard does not apply to final states
+    assert_eq!(sm.process_event(Events::Abort), Err(Error::MachineFinished));
+    assert!(matches!(sm.state(), &States::Done));
+
+    let mut sm = StateMachine::new(Context);
+    sm.process_event(Events::Abort).unwrap();
+    assert!(sm.is_finished());
+    assert_eq!(sm.process_event(Events::Start), Err(Error::MachineFinished));
+}
+
+#[test]
+fn state_machine_from_file() {
+    smlang::statemachine_file!("tests/machines/turnstile.sm");
+
+    struct Context {
+        unlocked: u32,
+    }
+    impl TurnstileStateMachineContext for Context {
+        fn valid_coin(&self) -> Result<bool, ()> {
+            Ok(true)
+        }
+        fn unlock(&mut self) -> Result<(), ()> {
+            self.unlocked += 1;
+            Ok(())
+        }
+        fn lock(&mut self) -> Result<(), ()> {
+            Ok(())
+        }
+    }
+
+    let mut sm = TurnstileStateMachine::new(Context { unlocked: 0 });
+    sm.process_event(TurnstileEvents::Push).unwrap();
+    assert!(matches!(sm.state(), &TurnstileStates::Locked));
+
+    sm.process_event(TurnstileEvents::Coin).unwrap();
+    assert!(matches!(sm.state(), &TurnstileStates::Unlocked));
+    assert_eq!(sm.context().unlocked, 1);
+
+    sm.process_event(TurnstileEvents::Push).unwrap();
+    assert!(matches!(sm.state(), &TurnstileStates::Locked));
+}
+
+#[cfg(feature = "scxml")]
+#[test]
+fn state_machine_from_scxml() {
+    smlang::statemachine_scxml!("tests/machines/door.scxml");
+
+    #[derive(Default)]
+    struct Context {
+        unlocked: bool,
+        light: bool,
+        opened: u32,
+    }
+    impl DoorStateMachineContext for Context {
+        fn unlocked(&self) -> Result<bool, ()> {
+            Ok(self.unlocked)
+        }
+        fn open(&mut self) -> Result<(), ()> {
+            self.opened += 1;
+            Ok(())
+        }
+        fn light_on(&mut self) {
+            self.light = true;
+        }
+        fn light_off(&mut self) {
+            self.light = false;
+        }
+    }
+
+    let mut sm = DoorStateMachine::new(Context::default());
+    assert!(matches!(
+        sm.process_event(DoorEvents::Open),
+        Err(DoorError::TransitionsFailed)
+    ));
+
+    sm.context_mut().unlocked = true;
+    sm.process_event(DoorEvents::Open).unwrap();
+    assert!(matches!(sm.state(), &DoorStates::Opened));
+    assert!(sm.context().light);
+    assert_eq!(sm.context().opened, 1);
+
+    sm.process_event(DoorEvents::Close).unwrap();
+    assert!(!sm.context().light);
+
+    sm.process_event(DoorEvents::Break).unwrap();
+    assert!(sm.is_finished());
+}
+
+#[test]
+fn generic_state_machine() {
+    #[derive(Debug)]
+    pub struct Buffer<T, const N: usize> {
+        items: [T; N],
+        len: usize,
+    }
+
+    statemachine! {
+        name: Sampler,
+        generics: <T: Copy + Default, const N: usize>,
+        transitions: {
+            *Idle + Start / start = Collecting(Buffer<T, N>),
+            Collecting(Buffer<T, N>) + Sample(T) [has_room] / store = Collecting(Buffer<T, N>),
+            Collecting(Buffer<T, N>) + Sample(T) = Full,
+        }
+    }
+
+    struct Context;
+    impl<T: Copy + Default, const N: usize> SamplerStateMachineContext<T, N> for Context {
+        fn start(&mut self) -> Result<Buffer<T, N>, ()> {
+            Ok(Buffer {
+                items: [T::default(); N],
+                len: 0,
+            })
+        }
+        fn has_room(&self, buffer: &Buffer<T, N>, _: &T) -> Result<bool, ()> {
+            Ok(buffer.len < N)
+        }
+        fn store(&mut self, buffer: &Buffer<T, N>, sample: T) -> Result<Buffer<T, N>, ()> {
+            let mut items = buffer.items;
+            items[buffer.len] = sample;
+            Ok(Buffer {
+                items,
+                len: buffer.len + 1,
+            })
+        }
+    }
+
+    let mut sm: SamplerStateMachine<_, u8, 2> = SamplerStateMachine::new(Context);
+    sm.process_event(SamplerEvents::Start).unwrap();
+    sm.process_event(SamplerEvents::Sample(3)).unwrap();
+    sm.process_event(SamplerEvents::Sample(5)).unwrap();
+    assert!(matches!(
+        sm.state(),
+        SamplerStates::Collecting(Buffer {
+            items: [3, 5],
+            len: 2
+        })
+    ));
+
+    sm.process_event(SamplerEvents::Sample(7)).unwrap();
+    assert!(matches!(sm.state(), SamplerStates::Full));
+}
+
+#[test]
+fn generics_named_like_the_context() {
+    statemachine! {
+        name: Pair,
+        generics: <T, C, Context>,
+        transitions: {
+            *Empty + Fill / fill = Full((T, C, Context)),
+            Full((T, C, Context)) + Clear = Empty,
+        }
+    }
+
+    struct Filler;
+    impl PairStateMachineContext<u8, bool, char> for Filler {
+        fn fill(&mut self) -> Result<(u8, bool, char), ()> {
+            Ok((1, true, 'x'))
+        }
+    }
+
+    let mut sm: PairStateMachine<_, u8, bool, char> = PairStateMachine::new(Filler);
+    sm.process_event(PairEvents::Fill).unwrap();
+    assert!(matches!(sm.state(), PairStates::Full((1, true, 'x'))));
+    sm.process_event(PairEvents::Clear).unwrap();
+    assert!(matches!(sm.state(), PairStates::Empty));
+}
+
+#[test]
+fn visibility_and_module() {
+    statemachine! {
+        visibility: pub(crate),
+        module: turnstile,
+        transitions: {
+            *Locked + Coin = Unlocked,
+            Unlocked + Push = Locked,
+        }
+    }
+
+    struct Context;
+    impl turnstile::StateMachineContext for Context {}
+
+    let mut sm = turnstile::StateMachine::new(Context);
+    sm.process_event(turnstile::Events::Coin).unwrap();
+    assert!(matches!(sm.state(), turnstile::States::Unlocked));
+    sm.process_event(turnstile::Events::Push).unwrap();
+    assert!(matches!(sm.state(), turnstile::States::Locked));
+}
+
+#[test]
+fn module_with_data() {
+    // The generated module imports its parent module with `use super::*`, which does not see
+    // items declared in a function body, so the data types live in a module of their own
+    mod machine {
+        use smlang::statemachine;
+
+        #[derive(Debug, PartialEq)]
+        pub struct Coins(pub u32);
+
+        statemachine! {
+            module: turnstile,
+            transitions: {
+                *Locked + Coin(u32) / count = Unlocked(Coins),
+                Unlocked(Coins) + Push = Locked,
+            }
+        }
+    }
+    use machine::{turnstile, Coins};
+
+    struct Context;
+    impl turnstile::StateMachineContext for Context {
+        fn count(&mut self, coins: u32) -> Result<Coins, ()> {
+            Ok(Coins(coins))
+        }
+    }
+
+    let mut sm = turnstile::StateMachine::new(Context);
+    sm.process_event(turnstile::Events::Coin(2)).unwrap();
+    assert!(matches!(sm.state(), turnstile::States::Unlocked(Coins(2))));
+    sm.process_event(turnstile::Events::Push).unwrap();
+    assert!(matches!(sm.state(), turnstile::States::Locked));
+}
+
+#[test]
+fn machine_and_error_attributes() {
+    // Not `PartialEq`, which the error derives by default
+    #[derive(Debug, Clone)]
+    pub struct SensorFault(&'static str);
+
+    statemachine! {
+        states_attr: #[derive(Clone)],
+        machine_attr: #[derive(Clone)],
+        error_attr: #[derive(Debug, Clone)],
+        custom_error: true,
+        transitions: {
+            *Idle + Measure [sensor_ok] = Measuring,
+        }
+    }
+
+    #[derive(Clone)]
+    struct Context;
+    impl StateMachineContext for Context {
+        type Error = SensorFault;
+        fn sensor_ok(&self) -> Result<bool, SensorFault> {
+            Err(SensorFault("disconnected"))
+        }
+    }
+
+    let mut sm = StateMachine::new(Context);
+    let snapshot = sm.clone();
+    let error = match sm.process_event(Events::Measure) {
+        Err(error) => error.clone(),
+        Ok(_) => panic!("the guard should fail"),
+    };
+    assert!(matches!(
+        error,
+        Error::GuardFailed {
+            guard: "sensor_ok",
+            error: SensorFault("disconnected")
+        }
+    ));
+    assert!(matches!(snapshot.state(), States::Idle));
+}
+
+#[test]
+fn detailed_errors() {
+    statemachine! {
+        states_attr: #[derive(Debug)],
+        detailed_errors: true,
+        custom_error: true,
+        transitions: {
+            *Idle + Start(u32) [valid_speed] / start = Running,
+            Running + Stop [can_stop] = Idle,
+        }
+    }
+
+    struct Context;
+    impl StateMachineContext for Context {
+        type Error = &'static str;
+        fn valid_speed(&self, speed: &u32) -> Result<bool, &'static str> {
+            if *speed > 100 {
+                Err("too fast")
+            } else {
+                Ok(*speed > 0)
+            }
+        }
+        fn start(&mut self, _speed: u32) -> Result<(), &'static str> {
+            Ok(())
+        }
+        fn can_stop(&self) -> Result<bool, &'static str> {
+            Ok(false)
+        }
+    }
+
+    let mut sm = StateMachine::new(Context);
+
+    // The invalid event is handed back and can be processed again
+    let event = match sm.process_event(Events::Stop) {
+        Err(Error::InvalidEvent { state, event }) => {
+            assert_eq!(state, StateKind::Idle);
+            assert_eq!(event.kind(), EventKind::Stop);
+            event
+        }
+        _ => panic!("the event should be invalid"),
+    };
+    assert_eq!(
+        format!("{:?}", sm.process_event(event).unwrap_err()),
+        "InvalidEvent { state: Idle, event: Stop }"
+    );
+
+    assert_eq!(
+        sm.process_event(Events::Start(0)).unwrap_err(),
+        Error::TransitionsFailed {
+            state: StateKind::Idle,
+            event: EventKind::Start
+        }
+    );
+    assert_eq!(
+        sm.process_event(Events::Start(101)).unwrap_err(),
+        Error::GuardFailed {
+            state: StateKind::Idle,
+            event: EventKind::Start,
+            guard: "valid_speed",
+            error: "too fast"
+        }
+    );
+
+    sm.process_event(Events::Start(10)).unwrap();
+    assert_eq!(sm.state().kind(), StateKind::Running);
+}
+
+#[test]
+fn error_display_and_source() {
+    use std::error::Error as _;
+
+    mod plain {
+        use smlang::statemachine;
+
+        statemachine! {
+            transitions: {
+                *Idle + Start = Running,
+            }
+        }
+    }
+
+    mod detailed {
+        use smlang::statemachine;
+
+        #[derive(Debug)]
+        pub struct SensorFault;
+
+        impl core::fmt::Display for SensorFault {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                f.write_str("sensor disconnected")
+            }
+        }
+
+        impl std::error::Error for SensorFault {}
+
+        statemachine! {
+            detailed_errors: true,
+            custom_error: true,
+            transitions: {
+                *Idle + Measure [sensor_ok] = Measuring,
+            }
+        }
+    }
+
+    struct Context;
+    impl plain::StateMachineContext for Context {}
+    impl detailed::StateMachineContext for Context {
+        type Error = detailed::SensorFault;
+        fn sensor_ok(&self) -> Result<bool, detailed::SensorFault> {
+            Err(detailed::SensorFault)
+        }
+    }
+
+    // The errors compose with `?` into boxed errors
+    fn process(
+        sm: &mut plain::StateMachine<Context>,
+        event: plain::Events,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        sm.process_event(event)?;
+        Ok(())
+    }
+
+    let mut sm = plain::StateMachine::new(Context);
+    process(&mut sm, plain::Events::Start).unwrap();
+    let error = process(&mut sm, plain::Events::Start).unwrap_err();
+    assert_eq!(error.to_string(), "invalid event in the current state");
+    assert!(error.source().is_none());
+
+    let mut sm = detailed::StateMachine::new(Context);
+    let error = match sm.process_event(detailed::Events::Measure) {
+        Err(error) => error,
+        Ok(_) => panic!("the guard should fail"),
+    };
+    assert_eq!(
+        error.to_string(),
+        "guard `sensor_ok` of event `Measure` failed in state `Idle`"
+    );
+    assert_eq!(error.source().unwrap().to_string(), "sensor disconnected");
+}
+
+#[test]
+fn guard_results_are_logged() {
+    use std::cell::RefCell;
+
+    statemachine! {
+        transitions: {
+            *Idle + Start [ready && !(busy || faulty)] = Running,
+        }
+    }
+
+    #[derive(Default)]
+    struct Context {
+        faulty: bool,
+        log: RefCell<Vec<(&'static str, Result<bool, ()>)>>,
+    }
+    impl StateMachineContext for Context {
+        fn ready(&self) -> Result<bool, ()> {
+            Ok(true)
+        }
+        fn busy(&self) -> Result<bool, ()> {
+            Ok(false)
+        }
+        fn faulty(&self) -> Result<bool, ()> {
+            if self.faulty {
+                Err(())
+            } else {
+                Ok(false)
+            }
+        }
+        fn log_guard_result(&self, guard: &'static str, result: &Result<bool, ()>) {
+            self.log.borrow_mut().push((guard, *result));
+        }
+    }
+
+    let mut sm = StateMachine::new(Context {
+        faulty: true,
+        ..Default::default()
+    });
+    assert!(matches!(
+        sm.process_event(Events::Start),
+        Err(Error::GuardFailed {
+            guard: "faulty",
+            error: ()
+        })
+    ));
+    assert_eq!(
+        sm.context().log.take(),
+        [
+            ("ready", Ok(true)),
+            ("busy", Ok(false)),
+            ("faulty", Err(()))
+        ]
+    );
+
+    sm.context_mut().faulty = false;
+    sm.process_event(Events::Start).unwrap();
+    assert_eq!(
+        sm.context().log.take(),
+        [
+            ("ready", Ok(true)),
+            ("busy", Ok(false)),
+            ("faulty", Ok(false))
+        ]
+    );
+}
+
+#[test]
+fn infallible_guards_and_actions() {
+    statemachine! {
+        fallible: false,
+        custom_error: true,
+        transitions: {
+            *Idle + Start(u32) [valid_speed] / start = Running(u32),
+            Running(u32) + Stop [can_stop?] / stop? = Idle,
+        }
+    }
+
+    struct Context;
+    impl StateMachineContext for Context {
+        type Error = &'static str;
+        fn valid_speed(&self, speed: &u32) -> bool {
+            *speed > 0
+        }
+        fn start(&mut self, speed: u32) -> u32 {
+            speed
+        }
+        fn can_stop(&self, speed: &u32) -> Result<bool, &'static str> {
+            if *speed > 100 {
+                Err("too fast")
+            } else {
+                Ok(true)
+            }
+        }
+        fn stop(&mut self, _speed: &u32) -> Result<(), &'static str> {
+            Ok(())
+        }
+    }
+
+    let mut sm = StateMachine::new(Context);
+    assert!(matches!(
+        sm.process_event(Events::Start(0)),
+        Err(Error::TransitionsFailed)
+    ));
+    assert!(matches!(
+        sm.process_event(Events::Start(200)),
+        Ok(&States::Running(200))
+    ));
+    assert!(matches!(
+        sm.process_event(Events::Stop),
+        Err(Error::GuardFailed {
+            guard: "can_stop",
+            error: "too fast"
+        })
+    ));
+
+    statemachine! {
+        name: Marked,
+        transitions: {
+            *Idle + Start [ready!] / start! = Running,
+            Running + Stop [can_stop] / stop = Idle,
+        }
+    }
+
+    struct MarkedContext;
+    impl MarkedStateMachineContext for MarkedContext {
+        fn ready(&self) -> bool {
+            true
+        }
+        fn start(&mut self) {}
+        fn can_stop(&self) -> Result<bool, ()> {
+            Ok(true)
+        }
+        fn stop(&mut self) -> Result<(), ()> {
+            Ok(())
+        }
+    }
+
+    let mut sm = MarkedStateMachine::new(MarkedContext);
+    assert!(matches!(
+        sm.process_event(MarkedEvents::Start),
+        Ok(&MarkedStates::Running)
+    ));
+    assert!(matches!(
+        sm.process_event(MarkedEvents::Stop),
+        Ok(&MarkedStates::Idle)
+    ));
+}
+
+#[test]
+fn action_failure_policies() {
+    macro_rules! check_policy {
+        ($module:ident, [$($policy:tt)*], $state:ident, $log:expr) => {{
+            #[allow(dead_code)]
+            mod $module {
+                use smlang::statemachine;
+
+                statemachine! {
+                    action_failure: $($policy)*,
+                    lints: allow,
+                    transitions: {
+                        *Idle + Start / start = Running,
+                        Running + Stop = Idle,
+                        Fault + Reset = Idle,
+                    }
+                }
+
+                #[derive(Default)]
+                pub struct Context {
+                    pub log: Vec<&'static str>,
+                }
+
+                impl StateMachineContext for Context {
+                    fn start(&mut self) -> Result<(), ()> {
+                        self.log.push("start");
+                        Err(())
+                    }
+                    fn on_entry_idle(&mut self) {
+                        self.log.push("entry_idle");
+                    }
+                    fn on_exit_idle(&mut self) {
+                        self.log.push("exit_idle");
+                    }
+                    fn on_entry_fault(&mut self) {
+                        self.log.push("entry_fault");
+                    }
+                }
+            }
+
+            let mut sm = $module::StateMachine::new($module::Context::default());
+            assert!(matches!(
+                sm.process_event($module::Events::Start),
+                Err($module::Error::ActionFailed(()))
+            ));
+            assert!(matches!(sm.state(), $module::States::$state));
+            assert_eq!(sm.context().log, $log);
+        }};
+    }
+
+    check_policy!(exit_first, [exit_first], Idle, ["exit_idle", "start"]);
+    check_policy!(action_first, [action_first], Idle, ["start"]);
+    check_policy!(
+        reenter,
+        [reenter],
+        Idle,
+        ["exit_idle", "start", "entry_idle"]
+    );
+    check_policy!(
+        enter_fault,
+        [enter(Fault)],
+        Fault,
+        ["exit_idle", "start", "entry_fault"]
+    );
+}
+
+#[test]
+fn error_states() {
+    #[derive(Debug, PartialEq)]
+    pub struct SensorError(u8);
+
+    statemachine! {
+        custom_error: true,
+        on_error: Fault,
+        transitions: {
+            *Idle + Start [sensor_ok] / start = Running,
+            Running + Step / step = Running ! Stopped,
+            Fault(SensorError) + Reset = Idle,
+            Stopped + Reset = Idle,
+        }
+    }
+
+    #[derive(Default)]
+    struct Context {
+        sensor: Option<u8>,
+        log: Vec<&'static str>,
+    }
+    impl StateMachineContext for Context {
+        type Error = SensorError;
+        fn sensor_ok(&self) -> Result<bool, SensorError> {
+            match self.sensor {
+                Some(error) => Err(SensorError(error)),
+                None => Ok(true),
+            }
+        }
+        fn start(&mut self) -> Result<(), SensorError> {
+            Ok(())
+        }
+        fn step(&mut self) -> Result<(), SensorError> {
+            Err(SensorError(2))
+        }
+        fn on_exit_idle(&mut self) {
+            self.log.push("exit_idle");
+        }
+        fn on_exit_running(&mut self) {
+            self.log.push("exit_running");
+        }
+        fn on_entry_fault(&mut self) {
+            self.log.push("entry_fault");
+        }
+        fn on_entry_stopped(&mut self) {
+            self.log.push("entry_stopped");
+        }
+    }
+
+    // The error of the guard is passed to the data of the error state
+    let mut sm = StateMachine::new(Context {
+        sensor: Some(1),
+        ..Default::default()
+    });
+    assert!(matches!(
+        sm.process_event(Events::Start),
+        Ok(&States::Fault(SensorError(1)))
+    ));
+    assert_eq!(sm.context().log, ["exit_idle", "entry_fault"]);
+
+    // The error state of the transition replaces `on_error`
+    let mut sm = StateMachine::new(Context::default());
+    sm.process_event(Events::Start).unwrap();
+    sm.context_mut().log.clear();
+    assert!(matches!(
+        sm.process_event(Events::Step),
+        Ok(&States::Stopped)
+    ));
+    assert_eq!(sm.context().log, ["exit_running", "entry_stopped"]);
+    sm.process_event(Events::Reset).unwrap();
+}
+
+#[test]
+fn error_state_conversion_only_bounds_event_processing() {
+    statemachine! {
+        name: Probe,
+        generics: <E>,
+        on_error: Failed,
+        transitions: {
+            *Idle + Run [check] = Done,
+            Failed(E) + Reset = Idle,
+            Done + Reset = Idle,
+        }
+    }
+
+    struct Context;
+    impl<E> ProbeStateMachineContext<E> for Context {
+        fn check(&self) -> Result<bool, ()> {
+            Ok(true)
+        }
+    }
+
+    // `String` cannot be converted from the `()` error of the guard, which only rules out
+    // processing events
+    let sm: ProbeStateMachine<_, String> = ProbeStateMachine::new(Context);
+    assert!(matches!(sm.state(), ProbeStates::Idle));
+
+    let mut sm: ProbeStateMachine<_, ()> = ProbeStateMachine::new(Context);
+    assert!(matches!(
+        sm.process_event(ProbeEvents::Run),
+        Ok(&ProbeStates::Done)
+    ));
+    assert!(matches!(
+        sm.process_event(ProbeEvents::Reset),
+        Ok(&ProbeStates::Idle)
+    ));
+}